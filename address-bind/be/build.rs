@@ -0,0 +1,4 @@
+// embedded migrations must be rebuilt when a migration is added
+fn main() {
+    println!("cargo:rerun-if-changed=migrations");
+}
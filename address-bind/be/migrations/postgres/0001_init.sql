@@ -0,0 +1,38 @@
+-- baseline schema, IF NOT EXISTS adopts databases created before migrations
+CREATE TABLE IF NOT EXISTS sync_status (height BIGINT PRIMARY KEY);
+
+CREATE TABLE IF NOT EXISTS bind_info (
+    from_addr TEXT,
+    to_addr TEXT,
+    timestamp BIGINT,
+    height BIGINT,
+    tx_index INTEGER,
+    UNIQUE(from_addr, to_addr, timestamp)
+);
+
+-- provenance of each binding, so it can be re-verified independently
+ALTER TABLE bind_info
+    ADD COLUMN IF NOT EXISTS tx_hash TEXT,
+    ADD COLUMN IF NOT EXISTS block_hash TEXT,
+    ADD COLUMN IF NOT EXISTS from_lock BYTEA,
+    ADD COLUMN IF NOT EXISTS from_lock_hash TEXT,
+    ADD COLUMN IF NOT EXISTS to_lock BYTEA,
+    ADD COLUMN IF NOT EXISTS to_lock_hash TEXT,
+    ADD COLUMN IF NOT EXISTS sig_scheme TEXT,
+    ADD COLUMN IF NOT EXISTS payload BYTEA;
+
+CREATE TABLE IF NOT EXISTS rejected_bind (
+    tx_hash TEXT PRIMARY KEY,
+    height BIGINT NOT NULL,
+    tx_index INTEGER NOT NULL,
+    to_addr TEXT NOT NULL,
+    from_addr TEXT,
+    reason TEXT NOT NULL,
+    message TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS block_info (
+    height BIGINT PRIMARY KEY,
+    hash TEXT NOT NULL,
+    parent_hash TEXT NOT NULL
+);
@@ -0,0 +1,11 @@
+-- /by_from: WHERE from_addr = $1 ORDER BY height DESC, tx_index DESC
+CREATE INDEX IF NOT EXISTS bind_info_from_idx ON bind_info (from_addr, height DESC, tx_index DESC);
+
+-- /by_to: WHERE to_addr = $1, DISTINCT ON (from_addr) ORDER BY from_addr, height DESC, tx_index DESC
+CREATE INDEX IF NOT EXISTS bind_info_to_idx ON bind_info (to_addr, from_addr, height DESC, tx_index DESC);
+
+-- rollback on chain reorganization deletes by height
+CREATE INDEX IF NOT EXISTS bind_info_height_idx ON bind_info (height);
+
+CREATE INDEX IF NOT EXISTS rejected_bind_to_idx ON rejected_bind (to_addr, height DESC, tx_index DESC);
+CREATE INDEX IF NOT EXISTS rejected_bind_height_idx ON rejected_bind (height);
@@ -0,0 +1,24 @@
+-- binding in effect for each from: latest signed timestamp, then latest in chain order
+CREATE TABLE IF NOT EXISTS active_binding (
+    from_addr TEXT PRIMARY KEY,
+    to_addr TEXT NOT NULL,
+    timestamp BIGINT NOT NULL,
+    height BIGINT NOT NULL,
+    tx_index INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS active_binding_to_idx ON active_binding (to_addr, from_addr);
+
+-- rollback on chain reorganization deletes by height
+CREATE INDEX IF NOT EXISTS active_binding_height_idx ON active_binding (height);
+
+INSERT INTO active_binding (from_addr, to_addr, timestamp, height, tx_index)
+SELECT from_addr, to_addr, timestamp, height, tx_index FROM (
+    SELECT *, ROW_NUMBER() OVER (
+        PARTITION BY from_addr ORDER BY timestamp DESC, height DESC, tx_index DESC
+    ) AS rank
+    FROM bind_info
+    WHERE from_addr IS NOT NULL
+) AS ranked
+WHERE rank = 1
+ON CONFLICT (from_addr) DO NOTHING;
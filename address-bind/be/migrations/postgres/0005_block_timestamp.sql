@@ -0,0 +1,4 @@
+-- block timestamp in milliseconds, resolves at_timestamp queries to a height.
+-- null for blocks indexed before it was recorded
+ALTER TABLE block_info ADD COLUMN IF NOT EXISTS timestamp BIGINT;
+CREATE INDEX IF NOT EXISTS block_info_timestamp_idx ON block_info (timestamp);
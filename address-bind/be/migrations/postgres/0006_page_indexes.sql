@@ -0,0 +1,3 @@
+-- /by_to pages: WHERE to_addr = $1 ORDER BY height DESC, tx_index DESC
+DROP INDEX IF EXISTS active_binding_to_idx;
+CREATE INDEX IF NOT EXISTS active_binding_to_page_idx ON active_binding (to_addr, height DESC, tx_index DESC);
@@ -0,0 +1,3 @@
+-- lookup of the address of a lock hash, queries may name a lock by its hash
+CREATE INDEX IF NOT EXISTS bind_info_from_lock_hash_idx ON bind_info (from_lock_hash);
+CREATE INDEX IF NOT EXISTS bind_info_to_lock_hash_idx ON bind_info (to_lock_hash);
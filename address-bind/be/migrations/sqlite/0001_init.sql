@@ -0,0 +1,35 @@
+-- same schema as the postgres backend, in sqlite types
+CREATE TABLE IF NOT EXISTS sync_status (height INTEGER PRIMARY KEY);
+
+CREATE TABLE IF NOT EXISTS bind_info (
+    from_addr TEXT,
+    to_addr TEXT,
+    timestamp INTEGER,
+    height INTEGER,
+    tx_index INTEGER,
+    tx_hash TEXT,
+    block_hash TEXT,
+    from_lock BLOB,
+    from_lock_hash TEXT,
+    to_lock BLOB,
+    to_lock_hash TEXT,
+    sig_scheme TEXT,
+    payload BLOB,
+    UNIQUE(from_addr, to_addr, timestamp)
+);
+
+CREATE TABLE IF NOT EXISTS rejected_bind (
+    tx_hash TEXT PRIMARY KEY,
+    height INTEGER NOT NULL,
+    tx_index INTEGER NOT NULL,
+    to_addr TEXT NOT NULL,
+    from_addr TEXT,
+    reason TEXT NOT NULL,
+    message TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS block_info (
+    height INTEGER PRIMARY KEY,
+    hash TEXT NOT NULL,
+    parent_hash TEXT NOT NULL
+);
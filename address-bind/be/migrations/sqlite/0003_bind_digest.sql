@@ -0,0 +1,6 @@
+-- blake2b of the signed BindInfo, a message binds at most once
+ALTER TABLE bind_info ADD COLUMN digest TEXT;
+CREATE UNIQUE INDEX IF NOT EXISTS bind_info_digest_idx ON bind_info (digest);
+
+-- timestamp of the latest binding of a from, a binding must not be older
+CREATE INDEX IF NOT EXISTS bind_info_from_timestamp_idx ON bind_info (from_addr, timestamp DESC);
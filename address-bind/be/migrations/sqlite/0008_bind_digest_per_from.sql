@@ -0,0 +1,4 @@
+-- a v1 BindInfo does not name its from, so several froms may sign the same one.
+-- a message binds at most once per from
+DROP INDEX IF EXISTS bind_info_digest_idx;
+CREATE UNIQUE INDEX IF NOT EXISTS bind_info_from_digest_idx ON bind_info (from_addr, digest);
@@ -0,0 +1,4 @@
+-- lock hash of the to of a rejected bind tx, queries may name a lock by its hash.
+-- backfilled at startup for rows rejected before it was recorded
+ALTER TABLE rejected_bind ADD COLUMN to_lock_hash TEXT;
+CREATE INDEX IF NOT EXISTS rejected_bind_to_lock_hash_idx ON rejected_bind (to_lock_hash);
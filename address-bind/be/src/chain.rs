@@ -0,0 +1,308 @@
+use async_trait::async_trait;
+use ckb_jsonrpc_types::{
+    BlockNumber, BlockView, CellOutput, Either, OutPoint, Transaction, TransactionView,
+};
+use ckb_sdk::rpc::CkbRpcClient;
+use ckb_types::{H256, packed, prelude::Unpack};
+use color_eyre::{Result, eyre::eyre};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+};
+
+// where the verifier and the indexer read chain data from
+#[async_trait]
+pub trait ChainSource: Send + Sync {
+    async fn get_transaction(&self, tx_hash: &H256) -> Result<Option<Transaction>>;
+
+    async fn get_block(&self, height: u64) -> Result<Option<BlockView>>;
+
+    async fn get_tip_block_number(&self) -> Result<u64>;
+
+    // output spent by an input, looked up from its transaction by default
+    async fn get_previous_output(&self, out_point: &OutPoint) -> Result<Option<CellOutput>> {
+        let index: u32 = out_point.index.into();
+        Ok(self
+            .get_transaction(&out_point.tx_hash)
+            .await?
+            .and_then(|tx| tx.outputs.get(index as usize).cloned()))
+    }
+}
+
+#[async_trait]
+impl ChainSource for CkbRpcClient {
+    async fn get_transaction(&self, tx_hash: &H256) -> Result<Option<Transaction>> {
+        let tx = CkbRpcClient::get_transaction(self, tx_hash.clone())
+            .map_err(|e| eyre!("Failed to get transaction: {e}"))?
+            .and_then(|tx| tx.transaction)
+            .and_then(|tx| match tx.inner {
+                Either::Left(tx_view) => Some(tx_view.inner),
+                Either::Right(_) => None,
+            });
+        Ok(tx)
+    }
+
+    async fn get_block(&self, height: u64) -> Result<Option<BlockView>> {
+        self.get_block_by_number(BlockNumber::from(height))
+            .map_err(|e| eyre!("Failed to get block {height}: {e}"))
+    }
+
+    async fn get_tip_block_number(&self) -> Result<u64> {
+        Ok(CkbRpcClient::get_tip_block_number(self)
+            .map_err(|e| eyre!("Failed to get tip block number: {e}"))?
+            .value())
+    }
+}
+
+// chain data kept in memory, for tests and for transactions not on chain
+#[derive(Debug, Default, Clone)]
+pub struct MemoryChain {
+    transactions: HashMap<H256, Transaction>,
+    // outputs known without their transaction
+    outputs: HashMap<(H256, u32), CellOutput>,
+    blocks: BTreeMap<u64, BlockView>,
+}
+
+impl MemoryChain {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert_transaction(&mut self, tx: Transaction) -> H256 {
+        let hash: H256 = packed::Transaction::from(tx.clone())
+            .calc_tx_hash()
+            .unpack();
+        self.transactions.insert(hash.clone(), tx);
+        hash
+    }
+
+    pub fn insert_output(&mut self, out_point: OutPoint, output: CellOutput) {
+        self.outputs
+            .insert((out_point.tx_hash, out_point.index.into()), output);
+    }
+
+    // transactions of the block are inserted too
+    #[cfg(test)]
+    pub fn insert_block(&mut self, block: BlockView) {
+        for tx in &block.transactions {
+            self.transactions.insert(tx.hash.clone(), tx.inner.clone());
+        }
+        self.blocks.insert(block.header.inner.number.value(), block);
+    }
+}
+
+#[async_trait]
+impl ChainSource for MemoryChain {
+    async fn get_transaction(&self, tx_hash: &H256) -> Result<Option<Transaction>> {
+        Ok(self.transactions.get(tx_hash).cloned())
+    }
+
+    async fn get_block(&self, height: u64) -> Result<Option<BlockView>> {
+        Ok(self.blocks.get(&height).cloned())
+    }
+
+    async fn get_tip_block_number(&self) -> Result<u64> {
+        Ok(self.blocks.keys().next_back().copied().unwrap_or_default())
+    }
+
+    async fn get_previous_output(&self, out_point: &OutPoint) -> Result<Option<CellOutput>> {
+        let index: u32 = out_point.index.into();
+        if let Some(output) = self.outputs.get(&(out_point.tx_hash.clone(), index)) {
+            return Ok(Some(output.clone()));
+        }
+        Ok(self
+            .transactions
+            .get(&out_point.tx_hash)
+            .and_then(|tx| tx.outputs.get(index as usize).cloned()))
+    }
+}
+
+// output spent by the transaction, as listed in a prev outputs file
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PrevOutput {
+    pub out_point: OutPoint,
+    pub output: CellOutput,
+}
+
+// self-contained transaction for offline verification
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct TxBundle {
+    pub transaction: Transaction,
+    #[serde(default)]
+    pub prev_outputs: Vec<PrevOutput>,
+    // full transactions of the inputs, instead of or in addition to prev_outputs
+    #[serde(default)]
+    pub prev_transactions: Vec<Transaction>,
+}
+
+impl TxBundle {
+    // tx_file is a bundle, a Transaction or a TransactionView in rpc json format,
+    // prev_outputs_file a list of PrevOutput
+    pub fn load(tx_file: &Path, prev_outputs_file: Option<&Path>) -> Result<Self> {
+        let content = std::fs::read_to_string(tx_file)
+            .map_err(|e| eyre!("read {} failed: {e}", tx_file.display()))?;
+        let mut bundle = if let Ok(bundle) = serde_json::from_str::<TxBundle>(&content) {
+            bundle
+        } else {
+            let transaction = serde_json::from_str::<Transaction>(&content)
+                .or_else(|_| {
+                    serde_json::from_str::<TransactionView>(&content).map(|view| view.inner)
+                })
+                .map_err(|e| eyre!("parse {} failed: {e}", tx_file.display()))?;
+            TxBundle {
+                transaction,
+                prev_outputs: vec![],
+                prev_transactions: vec![],
+            }
+        };
+        if let Some(path) = prev_outputs_file {
+            let content = std::fs::read_to_string(path)
+                .map_err(|e| eyre!("read {} failed: {e}", path.display()))?;
+            let prev_outputs: Vec<PrevOutput> = serde_json::from_str(&content)
+                .map_err(|e| eyre!("parse {} failed: {e}", path.display()))?;
+            bundle.prev_outputs.extend(prev_outputs);
+        }
+        Ok(bundle)
+    }
+
+    // chain holding only what the bundle carries
+    pub fn chain(&self) -> MemoryChain {
+        let mut chain = MemoryChain::new();
+        for tx in &self.prev_transactions {
+            chain.insert_transaction(tx.clone());
+        }
+        for prev in &self.prev_outputs {
+            chain.insert_output(prev.out_point.clone(), prev.output.clone());
+        }
+        chain
+    }
+}
+
+// json fixtures as returned by the ckb rpc:
+// <dir>/transactions/0x<tx_hash>.json, a Transaction
+// <dir>/blocks/<height>.json, a BlockView
+#[derive(Debug, Clone)]
+pub struct FixtureChain {
+    dir: PathBuf,
+}
+
+impl FixtureChain {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    fn read<T: serde::de::DeserializeOwned>(&self, path: PathBuf) -> Result<Option<T>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        let content = std::fs::read_to_string(&path)
+            .map_err(|e| eyre!("read {} failed: {e}", path.display()))?;
+        let value = serde_json::from_str(&content)
+            .map_err(|e| eyre!("parse {} failed: {e}", path.display()))?;
+        Ok(Some(value))
+    }
+}
+
+#[async_trait]
+impl ChainSource for FixtureChain {
+    async fn get_transaction(&self, tx_hash: &H256) -> Result<Option<Transaction>> {
+        self.read(
+            self.dir
+                .join("transactions")
+                .join(format!("{tx_hash:#x}.json")),
+        )
+    }
+
+    async fn get_block(&self, height: u64) -> Result<Option<BlockView>> {
+        self.read(self.dir.join("blocks").join(format!("{height}.json")))
+    }
+
+    async fn get_tip_block_number(&self) -> Result<u64> {
+        let Ok(entries) = std::fs::read_dir(self.dir.join("blocks")) else {
+            return Ok(0);
+        };
+        Ok(entries
+            .filter_map(|entry| {
+                let name = entry.ok()?.file_name();
+                name.to_str()?.strip_suffix(".json")?.parse::<u64>().ok()
+            })
+            .max()
+            .unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ckb_types::{
+        core::TransactionBuilder,
+        packed::CellOutput as PackedCellOutput,
+        prelude::{Builder, Entity, Pack},
+    };
+
+    fn tx_hash(tx: &Transaction) -> H256 {
+        packed::Transaction::from(tx.clone())
+            .calc_tx_hash()
+            .unpack()
+    }
+
+    fn transaction(capacity: u64) -> Transaction {
+        TransactionBuilder::default()
+            .output(
+                PackedCellOutput::new_builder()
+                    .capacity(capacity.pack())
+                    .build(),
+            )
+            .output_data(Default::default())
+            .build()
+            .data()
+            .into()
+    }
+
+    async fn check_chain(chain: &dyn ChainSource, hash: &H256) -> Result<()> {
+        let tx = chain.get_transaction(hash).await?.unwrap();
+        assert_eq!(&tx_hash(&tx), hash);
+
+        let out_point = OutPoint {
+            tx_hash: hash.clone(),
+            index: 0.into(),
+        };
+        let output = chain.get_previous_output(&out_point).await?.unwrap();
+        assert_eq!(output.capacity.value(), 100);
+
+        let missing = OutPoint {
+            tx_hash: hash.clone(),
+            index: 1.into(),
+        };
+        assert!(chain.get_previous_output(&missing).await?.is_none());
+        assert!(chain.get_transaction(&H256::default()).await?.is_none());
+        assert!(chain.get_block(1).await?.is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_memory_chain() -> Result<()> {
+        let mut chain = MemoryChain::new();
+        let hash = chain.insert_transaction(transaction(100));
+        check_chain(&chain, &hash).await
+    }
+
+    #[tokio::test]
+    async fn test_fixture_chain() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("abtest_fixture_{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("transactions"))?;
+        let tx = transaction(100);
+        let hash = tx_hash(&tx);
+        std::fs::write(
+            dir.join("transactions").join(format!("{hash:#x}.json")),
+            serde_json::to_string(&tx)?,
+        )?;
+
+        let chain = FixtureChain::new(&dir);
+        let ret = check_chain(&chain, &hash).await;
+        assert_eq!(chain.get_tip_block_number().await?, 0);
+        std::fs::remove_dir_all(&dir)?;
+        ret
+    }
+}
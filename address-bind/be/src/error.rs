@@ -8,12 +8,19 @@ use serde_json::json;
 
 #[derive(Debug)]
 pub enum AppError {
+    // invalid query parameters
+    BadRequest(String),
     Unknown(String),
 }
 
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
         let (status, error, error_message) = match self {
+            AppError::BadRequest(msg) => (
+                StatusCode::BAD_REQUEST,
+                "BadRequest",
+                string_to_static_str(msg),
+            ),
             AppError::Unknown(msg) => (
                 StatusCode::INTERNAL_SERVER_ERROR,
                 "Unknown",
@@ -41,3 +48,78 @@ where
 fn string_to_static_str(s: String) -> &'static str {
     Box::leak(s.into_boxed_str())
 }
+
+// error of verify_tx / get_tx
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerifyError {
+    // not a bind transaction: wrong shape, no input_type, unparsable molecule
+    NotBindTx(String),
+    // a bind transaction that failed verification
+    Rejected(RejectReason, String),
+    // chain, rpc or recovery service failure, may succeed on retry
+    Transient(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RejectReason {
+    LockMismatch,
+    BindToMismatch,
+    InvalidSignature,
+    UnsupportedSignature,
+    RecoveryFailed,
+    // signed timestamp too far from block timestamp, checked by indexer
+    TimestampOutOfRange,
+    // BindInfoV2 signed for another network
+    NetworkMismatch,
+    // BindInfoV2 signed for another application
+    DomainMismatch,
+    // signer is not the from declared in BindInfoV2
+    FromMismatch,
+    // BindInfo already bound by an earlier tx, checked by indexer
+    Replayed,
+    // signed before the current binding of from, checked by indexer
+    StaleTimestamp,
+}
+
+impl RejectReason {
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            RejectReason::LockMismatch => "lock_mismatch",
+            RejectReason::BindToMismatch => "bind_to_mismatch",
+            RejectReason::InvalidSignature => "invalid_signature",
+            RejectReason::UnsupportedSignature => "unsupported_signature",
+            RejectReason::RecoveryFailed => "recovery_failed",
+            RejectReason::TimestampOutOfRange => "timestamp_out_of_range",
+            RejectReason::NetworkMismatch => "network_mismatch",
+            RejectReason::DomainMismatch => "domain_mismatch",
+            RejectReason::FromMismatch => "from_mismatch",
+            RejectReason::Replayed => "replayed",
+            RejectReason::StaleTimestamp => "stale_timestamp",
+        }
+    }
+}
+
+impl VerifyError {
+    // machine readable failure reason
+    pub const fn reason(&self) -> &'static str {
+        match self {
+            VerifyError::NotBindTx(_) => "not_bind_tx",
+            VerifyError::Rejected(reason, _) => reason.as_str(),
+            VerifyError::Transient(_) => "transient",
+        }
+    }
+}
+
+impl std::fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VerifyError::NotBindTx(msg) => write!(f, "not bind tx: {msg}"),
+            VerifyError::Rejected(reason, msg) => {
+                write!(f, "rejected({}): {msg}", reason.as_str())
+            }
+            VerifyError::Transient(msg) => write!(f, "transient error: {msg}"),
+        }
+    }
+}
+
+impl std::error::Error for VerifyError {}
@@ -1,103 +1,618 @@
-use crate::{Indexer, error::AppError, verify::verify_tx};
-use ckb_jsonrpc_types::BlockNumber;
-use ckb_sdk::{NetworkType, rpc::CkbRpcClient};
+use crate::{
+    Indexer,
+    chain::ChainSource,
+    error::{AppError, RejectReason, VerifyError},
+    storage::{self, BindRow, IndexedBind, IndexedBlock, Page, RejectedBind, Storage},
+    verify::{BindRecord, VerifyOptions, calculate_address, normalize_address, verify_tx},
+};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use ckb_sdk::{Address, NetworkType};
+use ckb_types::{packed, prelude::Entity};
 use color_eyre::{Result, eyre::eyre};
 use common_x::restful::{
     axum::{
-        Router,
-        extract::{Path, State},
-        response::IntoResponse,
-        routing::get,
+        Json, Router,
+        extract::{Path, Query, State},
+        http::StatusCode,
+        response::{AppendHeaders, IntoResponse},
+        routing::{get, post},
     },
     ok,
 };
-use sqlx::{Executor, postgres::PgPoolOptions, query, query_as};
-use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeSet, HashMap},
+    str::FromStr,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
+};
 use tokio::time::sleep;
 use tower_http::{cors::CorsLayer, timeout::TimeoutLayer};
 
+#[derive(Debug, Default, Deserialize)]
+struct BindingParams {
+    // only return bindings with enough confirmations
+    #[serde(default)]
+    final_only: bool,
+    // resolve bindings as of the block at this height
+    at_height: Option<i64>,
+    // resolve bindings as of the last block with timestamp not after this, in milliseconds
+    at_timestamp: Option<u64>,
+}
+
+impl Indexer {
+    // number of blocks on top of height
+    fn confirmations(&self, height: i64) -> u64 {
+        self.tip_height
+            .load(Ordering::Relaxed)
+            .saturating_sub(height as u64)
+    }
+
+    fn binding_status(&self, height: i64) -> &'static str {
+        if self.confirmations(height) >= self.confirmations {
+            "final"
+        } else {
+            "pending"
+        }
+    }
+
+    // highest height allowed by params
+    async fn max_height(&self, params: &BindingParams) -> Result<i64, AppError> {
+        let at_height = match (params.at_height, params.at_timestamp) {
+            (Some(_), Some(_)) => {
+                return Err(AppError::BadRequest(
+                    "at_height and at_timestamp are exclusive".to_string(),
+                ));
+            }
+            (Some(height), None) => height,
+            (None, Some(timestamp)) => self
+                .storage
+                .height_at_timestamp(timestamp)
+                .await
+                .map_err(|e| eyre!("exec sql failed: {e}"))?
+                .ok_or_else(|| {
+                    AppError::BadRequest(format!(
+                        "timestamp {timestamp} precedes blocks indexed without timestamp"
+                    ))
+                })?,
+            (None, None) => i64::MAX,
+        };
+        if params.final_only {
+            let final_height = self
+                .tip_height
+                .load(Ordering::Relaxed)
+                .saturating_sub(self.confirmations) as i64;
+            Ok(at_height.min(final_height))
+        } else {
+            Ok(at_height)
+        }
+    }
+
+    // last height committed by the indexer, -1 before the first block. read once per
+    // request, so the whole request reads either active_binding or the ranked query
+    async fn indexed_height(&self) -> Result<i64, AppError> {
+        Ok(self
+            .storage
+            .sync_height()
+            .await
+            .map_err(|e| eyre!("exec sql failed: {e}"))?
+            .map_or(-1, |height| height as i64))
+    }
+
+    // max_height of params, i64::MAX if nothing above it is indexed so storage reads
+    // active_binding
+    async fn latest_or_max_height(&self, params: &BindingParams) -> Result<i64, AppError> {
+        let max_height = self.max_height(params).await?;
+        if max_height == i64::MAX || max_height >= self.indexed_height().await? {
+            Ok(i64::MAX)
+        } else {
+            Ok(max_height)
+        }
+    }
+}
+
+impl BindRow {
+    fn to_json(&self, state: &Indexer) -> serde_json::Value {
+        let script_json = |bytes: &Option<Vec<u8>>| {
+            bytes
+                .as_ref()
+                .and_then(|bytes| packed::Script::from_slice(bytes).ok())
+                .map(ckb_jsonrpc_types::Script::from)
+        };
+        serde_json::json!({
+            "from": self.from_addr,
+            "to": self.to_addr,
+            "timestamp": self.timestamp,
+            "height": self.height,
+            "tx_index": self.tx_index,
+            "confirmations": state.confirmations(self.height),
+            "status": state.binding_status(self.height),
+            "tx_hash": self.tx_hash,
+            "block_hash": self.block_hash,
+            "from_lock": script_json(&self.from_lock),
+            "from_lock_hash": self.from_lock_hash,
+            "to_lock": script_json(&self.to_lock),
+            "to_lock_hash": self.to_lock_hash,
+            "sig_scheme": self.sig_scheme,
+            "payload": self.payload.as_ref().map(|payload| format!("0x{}", hex::encode(payload))),
+            "digest": self.digest,
+        })
+    }
+}
+
+const DEFAULT_PAGE_SIZE: i64 = 100;
+const MAX_PAGE_SIZE: i64 = 1000;
+
+#[derive(Debug, Default, Deserialize)]
+struct PageParams {
+    // at most this many bindings, newest first
+    limit: Option<i64>,
+    // x-next-cursor of the previous page
+    cursor: Option<String>,
+    // only bindings included within these heights
+    min_height: Option<i64>,
+    max_height: Option<i64>,
+}
+
+// position after the last binding of a page, and the height all pages are resolved at
+#[derive(Debug, Serialize, Deserialize)]
+struct Cursor {
+    at_height: i64,
+    height: i64,
+    tx_index: i32,
+}
+
+impl Cursor {
+    fn encode(&self) -> String {
+        URL_SAFE_NO_PAD.encode(serde_json::to_vec(self).unwrap_or_default())
+    }
+
+    fn decode(cursor: &str) -> Result<Self, AppError> {
+        URL_SAFE_NO_PAD
+            .decode(cursor)
+            .ok()
+            .and_then(|json| serde_json::from_slice(&json).ok())
+            .ok_or_else(|| AppError::BadRequest(format!("invalid cursor {cursor}")))
+    }
+}
+
+fn is_lock_hash(address: &str) -> bool {
+    address.len() == 66
+        && address.starts_with("0x")
+        && address[2..].bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+// at most this many bindings, DEFAULT_PAGE_SIZE if not given
+fn check_limit(limit: Option<i64>) -> Result<i64, AppError> {
+    let limit = limit.unwrap_or(DEFAULT_PAGE_SIZE);
+    if !(1..=MAX_PAGE_SIZE).contains(&limit) {
+        return Err(AppError::BadRequest(format!(
+            "limit {limit} is not within 1 and {MAX_PAGE_SIZE}"
+        )));
+    }
+    Ok(limit)
+}
+
+impl Indexer {
+    // full format address of an address in any address encoding, a 0x lock hash lowercased
+    fn address_or_lock_hash(&self, address: &str) -> Result<String, AppError> {
+        if is_lock_hash(address) {
+            Ok(address.to_lowercase())
+        } else {
+            normalize_address(address, self.network)
+                .map_err(|e| AppError::BadRequest(format!("invalid address {address}: {e}")))
+        }
+    }
+
+    // full format address of an address in any address encoding or a 0x lock hash. a lock
+    // hash never seen is kept as is and matches nothing
+    async fn normalize_one(&self, address: &str) -> Result<String, AppError> {
+        let address = self.address_or_lock_hash(address)?;
+        if !is_lock_hash(&address) {
+            return Ok(address);
+        }
+        let bound = self
+            .storage
+            .addresses_by_lock_hash(std::slice::from_ref(&address))
+            .await
+            .map_err(|e| eyre!("exec sql failed: {e}"))?;
+        Ok(bound
+            .into_iter()
+            .next()
+            .map_or(address, |(_, address)| address))
+    }
+
+    // the page asked for. the first page pins the indexed height, so later pages are
+    // resolved the same while new blocks are indexed
+    async fn page(
+        &self,
+        params: &BindingParams,
+        page: &PageParams,
+        at_height: i64,
+    ) -> Result<Page, AppError> {
+        let limit = check_limit(page.limit)?;
+        let indexed_height = self.indexed_height().await?;
+        let (at_height, before) = match &page.cursor {
+            Some(cursor) => {
+                let cursor = Cursor::decode(cursor)?;
+                (cursor.at_height, Some((cursor.height, cursor.tx_index)))
+            }
+            None => {
+                let at_height = at_height.min(self.max_height(params).await?);
+                (at_height.min(indexed_height), None)
+            }
+        };
+        Ok(Page {
+            at_height,
+            latest: at_height >= indexed_height,
+            min_height: page.min_height.unwrap_or(0),
+            max_height: page.max_height.unwrap_or(i64::MAX),
+            before,
+            // one more to tell if there is a next page
+            limit: limit + 1,
+        })
+    }
+}
+
+// bindings of a page, with the total count and the cursor of the next page in headers
+fn page_response(
+    state: &Indexer,
+    page: &Page,
+    mut rows: Vec<BindRow>,
+    total: u64,
+) -> impl IntoResponse + use<> {
+    let mut headers = vec![("x-total-count", total.to_string())];
+    if rows.len() as i64 == page.limit {
+        rows.pop();
+        if let Some(last) = rows.last() {
+            let cursor = Cursor {
+                at_height: page.at_height,
+                height: last.height,
+                tx_index: last.tx_index,
+            };
+            headers.push(("x-next-cursor", cursor.encode()));
+        }
+    }
+    let result: Vec<_> = rows.iter().map(|row| row.to_json(state)).collect();
+    (AppendHeaders(headers), ok(result))
+}
+
 // define query handler
 async fn query_by_from(
     State(state): State<Indexer>,
     Path(from): Path<String>,
+    Query(params): Query<BindingParams>,
+    Query(page): Query<PageParams>,
 ) -> Result<impl IntoResponse, AppError> {
-    let rows: Vec<(String, i64, i32)> = query_as(
-        "SELECT to_addr, height, tx_index
-         FROM bind_info
-         WHERE from_addr = $1
-         ORDER BY height DESC, tx_index DESC",
-    )
-    .bind(&from)
-    .fetch_all(&state.db)
-    .await
-    .map_err(|e| eyre!("exec sql failed: {e}"))?;
-    let result: Vec<_> = rows
-        .iter()
-        .map(|row| {
-            serde_json::json!({
-                "to": row.0,
-                "height": row.1,
-                "tx_index": row.2
-            })
-        })
-        .collect();
+    let from = state.normalize_one(&from).await?;
+    let page = state.page(&params, &page, i64::MAX).await?;
+    let rows = state
+        .storage
+        .bindings_by_from(&from, &page)
+        .await
+        .map_err(|e| eyre!("exec sql failed: {e}"))?;
+    let total = state
+        .storage
+        .count_by_from(&from, &page)
+        .await
+        .map_err(|e| eyre!("exec sql failed: {e}"))?;
 
-    Ok(ok(result))
+    Ok(page_response(&state, &page, rows, total))
+}
+
+// froms whose binding in effect is to, as of at_height
+async fn bindings_by_to(
+    state: &Indexer,
+    to: &str,
+    page: &Page,
+) -> Result<impl IntoResponse + use<>, AppError> {
+    let rows = state
+        .storage
+        .bindings_by_to(to, page)
+        .await
+        .map_err(|e| eyre!("exec sql failed: {e}"))?;
+    let total = state
+        .storage
+        .count_by_to(to, page)
+        .await
+        .map_err(|e| eyre!("exec sql failed: {e}"))?;
+
+    Ok(page_response(state, page, rows, total))
 }
 
 async fn query_by_to(
     State(state): State<Indexer>,
     Path(to): Path<String>,
+    Query(params): Query<BindingParams>,
+    Query(page): Query<PageParams>,
 ) -> Result<impl IntoResponse, AppError> {
-    // Select for each from_addr the row with max height, and within that height the max tx_index
-    // Use DISTINCT ON to ensure we only return the latest record per from_addr
-    let rows: Vec<(String, i64, i32)> = query_as(
-        "SELECT DISTINCT ON (from_addr) from_addr, height, tx_index
-         FROM bind_info
-         WHERE to_addr = $1
-         ORDER BY from_addr, height DESC, tx_index DESC",
-    )
-    .bind(&to)
-    .fetch_all(&state.db)
-    .await
-    .map_err(|e| eyre!("exec sql failed: {e}"))?;
-    let result: Vec<_> = rows
-        .iter()
-        .map(|row| {
-            serde_json::json!({
-                "from": row.0,
-                "height": row.1,
-                "tx_index": row.2
-            })
+    let to = state.normalize_one(&to).await?;
+    let page = state.page(&params, &page, i64::MAX).await?;
+    bindings_by_to(&state, &to, &page).await
+}
+
+// addresses in one batch query, each one a bound sql parameter
+const MAX_BATCH_SIZE: usize = 5000;
+
+#[derive(Debug, Deserialize)]
+struct BatchQuery {
+    addresses: Vec<String>,
+    // at most this many bindings of each address, newest first
+    limit: Option<i64>,
+    #[serde(flatten)]
+    params: BindingParams,
+}
+
+impl BatchQuery {
+    // distinct addresses of the batch, and their full format address or lock hash
+    fn addresses(&self, state: &Indexer) -> Result<(Vec<String>, Vec<String>), AppError> {
+        let addresses: BTreeSet<_> = self.addresses.iter().cloned().collect();
+        if addresses.len() > MAX_BATCH_SIZE {
+            return Err(AppError::BadRequest(format!(
+                "{} addresses in batch, at most {MAX_BATCH_SIZE}",
+                addresses.len()
+            )));
+        }
+        let addresses: Vec<_> = addresses.into_iter().collect();
+        let normalized = addresses
+            .iter()
+            .map(|address| state.address_or_lock_hash(address))
+            .collect::<Result<_, _>>()?;
+        Ok((addresses, normalized))
+    }
+}
+
+// group rows by address and lock hash, every address of the batch is a key as requested,
+// even without bindings. normalized are the addresses or lock hashes rows are keyed by
+fn batch_result(
+    state: &Indexer,
+    addresses: Vec<String>,
+    normalized: &[String],
+    rows: Vec<BindRow>,
+    key: impl Fn(&BindRow) -> (&str, Option<&str>),
+) -> serde_json::Map<String, serde_json::Value> {
+    let mut bindings: HashMap<_, Vec<_>> = HashMap::new();
+    for row in &rows {
+        let json = row.to_json(state);
+        let (address, lock_hash) = key(row);
+        if let Some(lock_hash) = lock_hash {
+            bindings.entry(lock_hash).or_default().push(json.clone());
+        }
+        bindings.entry(address).or_default().push(json);
+    }
+    addresses
+        .into_iter()
+        .zip(normalized)
+        .map(|(address, normalized)| {
+            let bindings = bindings
+                .get(normalized.as_str())
+                .cloned()
+                .unwrap_or_default();
+            (address, bindings.into())
         })
+        .collect()
+}
+
+// query_by_from of many froms at once
+async fn query_by_from_batch(
+    State(state): State<Indexer>,
+    Json(batch): Json<BatchQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let (addresses, normalized) = batch.addresses(&state)?;
+    let limit = check_limit(batch.limit)?;
+    let froms: Vec<_> = normalized
+        .iter()
+        .cloned()
+        .collect::<BTreeSet<_>>()
+        .into_iter()
         .collect();
+    let max_height = state.max_height(&batch.params).await?;
+    let rows = if froms.is_empty() {
+        vec![]
+    } else {
+        state
+            .storage
+            .bindings_by_froms(&froms, max_height, limit)
+            .await
+            .map_err(|e| eyre!("exec sql failed: {e}"))?
+    };
 
-    Ok(ok(result))
+    Ok(ok(batch_result(
+        &state,
+        addresses,
+        &normalized,
+        rows,
+        |row| (&row.from_addr, row.from_lock_hash.as_deref()),
+    )))
 }
 
-// like query_by_to, but excludes rows with height greater than specified
+// query_by_to of many tos at once
+async fn query_by_to_batch(
+    State(state): State<Indexer>,
+    Json(batch): Json<BatchQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let (addresses, normalized) = batch.addresses(&state)?;
+    let limit = check_limit(batch.limit)?;
+    let tos: Vec<_> = normalized
+        .iter()
+        .cloned()
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect();
+    let max_height = state.latest_or_max_height(&batch.params).await?;
+    let rows = if tos.is_empty() {
+        vec![]
+    } else {
+        state
+            .storage
+            .bindings_by_tos(&tos, max_height, limit)
+            .await
+            .map_err(|e| eyre!("exec sql failed: {e}"))?
+    };
+
+    Ok(ok(batch_result(
+        &state,
+        addresses,
+        &normalized,
+        rows,
+        |row| (&row.to_addr, row.to_lock_hash.as_deref()),
+    )))
+}
+
+// binding in effect of from, null if from never bound
+async fn query_current(
+    State(state): State<Indexer>,
+    Path(from): Path<String>,
+    Query(params): Query<BindingParams>,
+) -> Result<impl IntoResponse, AppError> {
+    let from = state.normalize_one(&from).await?;
+    let row = state
+        .storage
+        .current_binding(&from, state.latest_or_max_height(&params).await?)
+        .await
+        .map_err(|e| eyre!("exec sql failed: {e}"))?;
+
+    Ok(ok(row.map(|row| row.to_json(&state))))
+}
+
+// like query_by_to, but as of the specified height
 async fn query_by_to_at_height(
     State(state): State<Indexer>,
     Path((to, height)): Path<(String, i64)>,
+    Query(params): Query<BindingParams>,
+    Query(page): Query<PageParams>,
 ) -> Result<impl IntoResponse, AppError> {
-    let rows: Vec<(String, i64, i32)> = query_as(
-        "SELECT DISTINCT ON (from_addr) from_addr, height, tx_index
-         FROM bind_info
-         WHERE to_addr = $1 AND height <= $2
-         ORDER BY from_addr, height DESC, tx_index DESC",
-    )
-    .bind(&to)
-    .bind(height)
-    .fetch_all(&state.db)
-    .await
-    .map_err(|e| eyre!("exec sql failed: {e}"))?;
+    let to = state.normalize_one(&to).await?;
+    let page = state.page(&params, &page, height).await?;
+    bindings_by_to(&state, &to, &page).await
+}
+
+// rows indexed before locks were recorded get them from their address, so they can be
+// queried by lock hash
+async fn backfill_locks(storage: &dyn Storage) -> Result<()> {
+    for address in storage.unhashed_addresses().await? {
+        match Address::from_str(&address) {
+            Ok(parsed) => {
+                let lock = packed::Script::from(&parsed);
+                let lock_hash = format!("{:#x}", lock.calc_script_hash());
+                storage
+                    .set_address_lock(&address, lock.as_slice(), &lock_hash)
+                    .await?;
+            }
+            Err(e) => warn!("skip backfilling lock of address {address}: {e}"),
+        }
+    }
+    Ok(())
+}
+
+// a binding must bring a BindInfo its from did not bind before, signed no earlier than the current
+// binding of its from, otherwise an old signature could be replayed to roll it back
+async fn check_replay(
+    storage: &dyn Storage,
+    indexed: &IndexedBlock,
+    record: &BindRecord,
+) -> Result<Option<(RejectReason, String)>> {
+    let digest = format!("0x{}", hex::encode(record.digest));
+    let consumed_by = match indexed
+        .bindings
+        .iter()
+        .find(|bind| bind.record.from == record.from && bind.record.digest == record.digest)
+    {
+        Some(bind) => Some(bind.tx_hash.clone()),
+        None => storage.digest_consumed_by(&record.from, &digest).await?,
+    };
+    if let Some(tx_hash) = consumed_by {
+        return Ok(Some((
+            RejectReason::Replayed,
+            format!(
+                "bind info {digest} already bound by {} in tx {tx_hash}",
+                record.from
+            ),
+        )));
+    }
+
+    let latest = indexed
+        .bindings
+        .iter()
+        .filter(|bind| bind.record.from == record.from)
+        .map(|bind| bind.record.timestamp)
+        .max()
+        .max(storage.latest_timestamp(&record.from).await?);
+    match latest {
+        Some(latest) if record.timestamp < latest => Ok(Some((
+            RejectReason::StaleTimestamp,
+            format!(
+                "timestamp {} is older than current binding at {latest}",
+                record.timestamp
+            ),
+        ))),
+        _ => Ok(None),
+    }
+}
+
+// return the common ancestor if the block at height does not extend the indexed chain
+async fn detect_reorg(
+    chain: &dyn ChainSource,
+    storage: &dyn Storage,
+    height: u64,
+    parent_hash: &str,
+) -> Result<Option<u64>> {
+    if height == 0 {
+        return Ok(None);
+    }
+    match storage.block_hash(height - 1).await? {
+        Some(indexed_parent_hash) if indexed_parent_hash != parent_hash => Ok(Some(
+            find_common_ancestor(chain, storage, height - 1).await?,
+        )),
+        _ => Ok(None),
+    }
+}
+
+// walk back from height until the indexed block hash matches the canonical chain
+async fn find_common_ancestor(
+    chain: &dyn ChainSource,
+    storage: &dyn Storage,
+    mut height: u64,
+) -> Result<u64> {
+    loop {
+        // nothing indexed below, nothing to compare
+        let Some(indexed_hash) = storage.block_hash(height).await? else {
+            return Ok(height);
+        };
+        let canonical_hash = chain
+            .get_block(height)
+            .await?
+            .map(|block| format!("{:#x}", block.header.hash));
+        if canonical_hash.as_deref() == Some(indexed_hash.as_str()) || height == 0 {
+            return Ok(height);
+        }
+        height -= 1;
+    }
+}
+
+async fn query_rejected_by_to(
+    State(state): State<Indexer>,
+    Path(to): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    let to = state.normalize_one(&to).await?;
+    let rows = state
+        .storage
+        .rejected_by_to(&to)
+        .await
+        .map_err(|e| eyre!("exec sql failed: {e}"))?;
     let result: Vec<_> = rows
         .iter()
         .map(|row| {
             serde_json::json!({
-                "from": row.0,
-                "height": row.1,
-                "tx_index": row.2
+                "tx_hash": row.tx_hash,
+                "height": row.height,
+                "tx_index": row.tx_index,
+                "from": row.from_addr,
+                "reason": row.reason,
+                "message": row.message
             })
         })
         .collect();
@@ -105,43 +620,117 @@ async fn query_by_to_at_height(
     Ok(ok(result))
 }
 
+#[derive(Debug, Default, Deserialize)]
+struct StatusParams {
+    // respond 503 if the index lags behind the chain tip by more blocks
+    max_lag: Option<u64>,
+}
+
+async fn query_status(
+    State(state): State<Indexer>,
+    Query(params): Query<StatusParams>,
+) -> Result<impl IntoResponse, AppError> {
+    let db_connected = state.storage.ping().await;
+    let indexed = if db_connected {
+        state
+            .storage
+            .last_block()
+            .await
+            .map_err(|e| eyre!("exec sql failed: {e}"))?
+    } else {
+        None
+    };
+    let indexed_height = indexed.as_ref().map(|(height, _)| *height);
+    let last_block_hash = indexed.and_then(|(_, hash)| hash);
+    // 0 until the indexer loop fetched the chain tip
+    let tip_height = Some(state.tip_height.load(Ordering::Relaxed)).filter(|tip| *tip > 0);
+    let lag = indexed_height
+        .zip(tip_height)
+        .map(|(height, tip)| tip.saturating_sub(height));
+
+    // recovery service is optional, any http response means it is up
+    let recovery_reachable = match &state.recovery_url {
+        Some(url) => Some(
+            reqwest::Client::new()
+                .get(format!("{url}/recover"))
+                .timeout(Duration::from_secs(2))
+                .send()
+                .await
+                .is_ok(),
+        ),
+        None => None,
+    };
+
+    let healthy = db_connected
+        && match params.max_lag {
+            Some(max_lag) => lag.is_some_and(|lag| lag <= max_lag),
+            None => true,
+        };
+    let status = if healthy {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    Ok((
+        status,
+        ok(serde_json::json!({
+            "healthy": healthy,
+            "indexed_height": indexed_height,
+            "tip_height": tip_height,
+            "lag": lag,
+            "last_block_hash": last_block_hash,
+            "confirmations": state.confirmations,
+            "db_connected": db_connected,
+            "recovery_service": state.recovery_url,
+            "recovery_reachable": recovery_reachable,
+        })),
+    ))
+}
+
 pub async fn server(
-    ckb_client: &CkbRpcClient,
+    chain: &dyn ChainSource,
     network_type: NetworkType,
     db_url: &str,
     start_height: u64,
     listen_port: u16,
-    recovery_url: &str,
+    confirmations: u64,
+    options: VerifyOptions,
 ) -> Result<()> {
-    let db = PgPoolOptions::new()
-        .max_connections(5)
-        .connect(db_url)
-        .await?;
+    let storage = storage::connect(db_url).await?;
 
-    // create table
-    db.execute("CREATE TABLE IF NOT EXISTS sync_status (height BIGINT PRIMARY KEY)")
-        .await?;
-    db.execute(
-        "CREATE TABLE IF NOT EXISTS bind_info (from_addr TEXT, to_addr TEXT, timestamp BIGINT, height BIGINT, tx_index INTEGER, UNIQUE(from_addr, to_addr, timestamp))",
-    ).await?;
-
-    // get last sync height
-    let mut current_height: u64 =
-        query_as("SELECT height FROM sync_status ORDER BY height DESC LIMIT 1")
-            .fetch_one(&db)
-            .await
-            .map(|r: (i32,)| r.0 as u64)
-            .unwrap_or(start_height);
+    storage.migrate().await?;
+    backfill_locks(storage.as_ref()).await?;
+
+    // resume from the block after the last committed one
+    let mut current_height = storage
+        .sync_height()
+        .await?
+        .map_or(start_height, |height| height + 1);
 
-    let indexer = Indexer { db: db.clone() };
+    let tip_height = Arc::new(AtomicU64::new(0));
+    let indexer = Indexer {
+        storage: storage.clone(),
+        tip_height: tip_height.clone(),
+        confirmations,
+        recovery_url: options.recovery_url.clone(),
+        network: network_type,
+    };
 
     tokio::spawn(async move {
         // start http server on listen_port
         let app = Router::new()
             .route("/by_from/{from}", get(query_by_from))
             .route("/by_to/{to}", get(query_by_to))
+            .route("/by_from/batch", post(query_by_from_batch))
+            .route("/by_to/batch", post(query_by_to_batch))
             .route("/by_to_at_height/{to}/{height}", get(query_by_to_at_height))
-            .layer((TimeoutLayer::new(Duration::from_secs(10)),))
+            .route("/current/{from}", get(query_current))
+            .route("/rejected/by_to/{to}", get(query_rejected_by_to))
+            .route("/status", get(query_status))
+            .layer((TimeoutLayer::with_status_code(
+                StatusCode::REQUEST_TIMEOUT,
+                Duration::from_secs(10),
+            ),))
             .layer(CorsLayer::permissive())
             .with_state(indexer);
 
@@ -152,9 +741,9 @@ pub async fn server(
 
     loop {
         // get latest block height
-        if let Ok(tip_block) = ckb_client.get_tip_block_number() {
+        if let Ok(tip_block) = chain.get_tip_block_number().await {
             // if already synced to latest height, wait for new block
-            let tip_block = tip_block.value();
+            tip_height.store(tip_block, Ordering::Relaxed);
             if current_height >= tip_block {
                 sleep(Duration::from_secs(1)).await;
                 continue;
@@ -169,12 +758,193 @@ pub async fn server(
             continue;
         }
 
-        // get block by number
-        let ret = ckb_client.get_block_by_number(BlockNumber::from(current_height));
+        match index_block(
+            chain,
+            storage.as_ref(),
+            network_type,
+            &options,
+            current_height,
+        )
+        .await
+        {
+            Ok(next_height) if next_height != current_height => current_height = next_height,
+            // block not found yet
+            Ok(_) => sleep(Duration::from_secs(1)).await,
+            Err(e) => {
+                error!("{e}");
+                sleep(Duration::from_secs(1)).await;
+            }
+        }
+    }
+}
+
+// index the block at height and return the next height to index,
+// which is above the common ancestor after a chain reorganization
+async fn index_block(
+    chain: &dyn ChainSource,
+    storage: &dyn Storage,
+    network_type: NetworkType,
+    options: &VerifyOptions,
+    height: u64,
+) -> Result<u64> {
+    // get block by number
+    let Some(block) = chain.get_block(height).await? else {
+        return Ok(height);
+    };
+    let block_hash = format!("{:#x}", block.header.hash);
+    let parent_hash = format!("{:#x}", block.header.inner.parent_hash);
+
+    // chain reorganization, parent of this block is not the indexed one
+    if let Some(ancestor) = detect_reorg(chain, storage, height, &parent_hash)
+        .await
+        .map_err(|e| eyre!("Failed to check reorg at height {height}: {e}"))?
+    {
+        warn!("chain reorg detected at height {height}, rollback to {ancestor}");
+        storage
+            .rollback(ancestor)
+            .await
+            .map_err(|e| eyre!("Failed to rollback to {ancestor}: {e}"))?;
+        return Ok(ancestor + 1);
+    }
+
+    let block_timestamp = u64::from(block.header.inner.timestamp);
+
+    // proc transactions in block
+    let mut indexed = IndexedBlock {
+        height,
+        hash: block_hash,
+        parent_hash,
+        timestamp: block_timestamp,
+        bindings: vec![],
+        rejected: vec![],
+    };
+    for (index, tx) in block.transactions.into_iter().enumerate() {
+        // ignore cellbase transaction
+        if index == 0 {
+            continue;
+        }
+
+        // verify transaction
+        match verify_tx(chain, network_type, &tx.inner, options).await {
+            Ok(record) => {
+                let timestamp = record.timestamp;
+                info!(
+                    "from: {}, to: {}, timestamp: {timestamp}, sig_scheme: {}",
+                    record.from, record.to, record.sig_scheme
+                );
+
+                // check timestamp is around current block timestamp, within 20min
+                if timestamp < block_timestamp - 20 * 60 * 1000
+                    || timestamp > block_timestamp + 20 * 60 * 1000
+                {
+                    error!(
+                        "timestamp {timestamp} is out of range, block_timestamp: {block_timestamp}"
+                    );
+                    indexed.rejected.push(RejectedBind {
+                        tx_hash: format!("{:#x}", tx.hash),
+                        tx_index: index,
+                        to: record.to,
+                        to_lock: record.to_lock,
+                        from: Some(record.from),
+                        reason: RejectReason::TimestampOutOfRange,
+                        message: format!(
+                            "timestamp {timestamp} is out of range, block_timestamp: {block_timestamp}"
+                        ),
+                    });
+                    continue;
+                }
+
+                // retry whole block later
+                if let Some((reason, message)) = check_replay(storage, &indexed, &record)
+                    .await
+                    .map_err(|e| {
+                        eyre!(
+                            "verify_tx {} is failed, retry block {height}, err: {e}",
+                            tx.hash
+                        )
+                    })?
+                {
+                    warn!("bind tx {} is rejected: {message}", tx.hash);
+                    indexed.rejected.push(RejectedBind {
+                        tx_hash: format!("{:#x}", tx.hash),
+                        tx_index: index,
+                        to: record.to,
+                        to_lock: record.to_lock,
+                        from: Some(record.from),
+                        reason,
+                        message,
+                    });
+                    continue;
+                }
+
+                indexed.bindings.push(IndexedBind {
+                    record,
+                    tx_index: index,
+                    tx_hash: format!("{:#x}", tx.hash),
+                });
+            }
+            // unrelated transaction
+            Err(VerifyError::NotBindTx(_)) => {}
+            Err(VerifyError::Rejected(reason, message)) => {
+                warn!(
+                    "verify_tx {} is rejected, reason: {}, err: {message}",
+                    tx.hash,
+                    reason.as_str()
+                );
+                // the sender of a bind tx transfers to itself
+                let to_lock: packed::Script = tx.inner.outputs[0].lock.clone().into();
+                indexed.rejected.push(RejectedBind {
+                    tx_hash: format!("{:#x}", tx.hash),
+                    tx_index: index,
+                    to: calculate_address(&to_lock, network_type).to_string(),
+                    to_lock,
+                    from: None,
+                    reason,
+                    message,
+                });
+            }
+            // retry whole block later
+            Err(e @ VerifyError::Transient(_)) => {
+                return Err(eyre!(
+                    "verify_tx {} is failed, retry block {height}, err: {e}",
+                    tx.hash
+                ));
+            }
+        }
+    }
+
+    // bindings, rejections, block record and sync height are committed together
+    storage
+        .commit_block(&indexed)
+        .await
+        .map_err(|e| eyre!("Failed to commit block {height}: {e}"))?;
+
+    Ok(height + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain::MemoryChain;
+    use crate::storage::tests::{block, record};
+    use crate::verify::tests::{TIMESTAMP, bind_tx, lock_of};
+    use ckb_jsonrpc_types::BlockNumber;
+    use ckb_sdk::rpc::CkbRpcClient;
+    use ckb_types::{
+        core::{BlockBuilder, EpochNumberWithFraction, TransactionBuilder},
+        prelude::{IntoTransactionView, Pack},
+    };
+    use secp256k1::SecretKey;
+
+    #[tokio::test]
+    async fn test_one() -> Result<()> {
+        common_x::log::init_log_filter("info");
+        let ckb_client = CkbRpcClient::new("https://testnet.ckb.dev/");
+        let ret = ckb_client.get_block_by_number(BlockNumber::from(18977278));
 
         if let Ok(Some(block)) = ret {
             let block_timestamp = u64::from(block.header.inner.timestamp);
-
+            info!("block_timestamp: {block_timestamp}");
             // proc transactions in block
             for (index, tx) in block.transactions.into_iter().enumerate() {
                 // ignore cellbase transaction
@@ -183,10 +953,20 @@ pub async fn server(
                 }
 
                 // verify transaction
-                match verify_tx(ckb_client, network_type, &tx.inner, recovery_url).await {
-                    Ok((from, to, timestamp)) => {
-                        info!("from: {from}, to: {to}, timestamp: {timestamp}");
-
+                match verify_tx(
+                    &ckb_client,
+                    NetworkType::Testnet,
+                    &tx.inner,
+                    &VerifyOptions::default(),
+                )
+                .await
+                {
+                    Ok(record) => {
+                        let timestamp = record.timestamp;
+                        info!(
+                            "from: {}, to: {}, timestamp: {timestamp}",
+                            record.from, record.to
+                        );
                         // check timestamp is around current block timestamp, within 20min
                         if timestamp < block_timestamp - 20 * 60 * 1000
                             || timestamp > block_timestamp + 20 * 60 * 1000
@@ -196,236 +976,281 @@ pub async fn server(
                             );
                             continue;
                         }
-
-                        // insert bind info to db
-                        if let Err(e) = db
-                            .execute(query(
-                                "INSERT INTO bind_info (from_addr, to_addr, timestamp, height, tx_index)
-                                 VALUES ($1, $2, $3, $4, $5)"
-                            )
-                            .bind(&from)
-                            .bind(&to)
-                            .bind(timestamp as i64)
-                            .bind(current_height as i64)
-                            .bind(index as i32))
-                        .await {
-                            error!("Failed to insert bind info: {e}");
-                        }
                     }
+                    Err(VerifyError::NotBindTx(_)) => {}
                     Err(e) => {
-                        if e.contains("get_tx failed")
-                            || e.contains("sig_bytes")
-                            || e.contains("timestamp is out of range")
-                        {
-                            error!("verify_tx {} is failed, err: {e}", tx.hash);
-                        }
+                        error!("verify_tx {} is failed, err: {e}", tx.hash);
                     }
                 }
             }
-
-            // update sync height
-            // not too frequently
-            if current_height.is_multiple_of(100)
-                && let Err(e) = db
-                    .execute(query(
-                        "INSERT INTO sync_status (height) VALUES ($1) ON CONFLICT (height) DO NOTHING;"
-                    )
-                    .bind(current_height as i64))
-                    .await
-            {
-                error!("Failed to update sync status: {e}");
-            }
-
-            current_height += 1;
         }
+        Ok(())
     }
-}
 
-#[tokio::test]
-async fn test_one() -> Result<()> {
-    common_x::log::init_log_filter("info");
-    let ckb_client = CkbRpcClient::new("https://testnet.ckb.dev/");
-    let ret = ckb_client.get_block_by_number(BlockNumber::from(18977278));
-
-    if let Ok(Some(block)) = ret {
-        let block_timestamp = u64::from(block.header.inner.timestamp);
-        info!("block_timestamp: {block_timestamp}");
-        // proc transactions in block
-        for (index, tx) in block.transactions.into_iter().enumerate() {
-            // ignore cellbase transaction
-            if index == 0 {
-                continue;
-            }
+    #[tokio::test]
+    async fn test_check_replay() -> Result<()> {
+        let storage = storage::connect("sqlite::memory:").await?;
+        storage.migrate().await?;
+        backfill_locks(storage.as_ref()).await?;
+        storage
+            .commit_block(&block(100, vec![(record("F1", "T1", 10), 1)]))
+            .await?;
 
-            // verify transaction
-            match verify_tx(&ckb_client, NetworkType::Testnet, &tx.inner).await {
-                Ok((from, to, timestamp)) => {
-                    info!("from: {from}, to: {to}, timestamp: {timestamp}");
-                    // check timestamp is around current block timestamp, within 20min
-                    if timestamp < block_timestamp - 20 * 60 * 1000
-                        || timestamp > block_timestamp + 20 * 60 * 1000
-                    {
-                        error!(
-                            "timestamp {timestamp} is out of range, block_timestamp: {block_timestamp}"
-                        );
-                        continue;
-                    }
-                }
-                Err(e) => {
-                    if e.contains("get_tx failed") {
-                        error!("verify_tx {} is failed, err: {e}", tx.hash);
-                    }
-                }
-            }
-        }
-    }
-    Ok(())
-}
+        // a new BindInfo, signed later than the current binding
+        let mut indexed = block(101, vec![]);
+        let newer = record("F1", "T2", 20);
+        assert_eq!(
+            check_replay(storage.as_ref(), &indexed, &newer).await?,
+            None
+        );
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use sqlx::{Pool, Postgres};
-
-    async fn get_test_db() -> Option<Pool<Postgres>> {
-        let db_url = std::env::var("TEST_DB_URL")
-            .or_else(|_| std::env::var("DB_URL"))
-            .ok()?;
-        PgPoolOptions::new()
-            .max_connections(5)
-            .connect(&db_url)
-            .await
-            .ok()
-    }
+        // the same BindInfo again, from the chain or earlier in the block
+        let replayed = check_replay(storage.as_ref(), &indexed, &record("F1", "T1", 10)).await?;
+        assert!(matches!(replayed, Some((RejectReason::Replayed, _))));
+        indexed.bindings = block(101, vec![(newer, 1)]).bindings;
+        let replayed = check_replay(storage.as_ref(), &indexed, &record("F1", "T2", 20)).await?;
+        assert!(matches!(replayed, Some((RejectReason::Replayed, _))));
 
-    fn test_schema(suffix: &str) -> String {
-        format!("abtest_{}_{}", std::process::id(), suffix)
-    }
+        // an old signature rolling the binding back
+        let stale = check_replay(storage.as_ref(), &indexed, &record("F1", "T1", 15)).await?;
+        assert!(matches!(stale, Some((RejectReason::StaleTimestamp, _))));
+        assert_eq!(
+            check_replay(storage.as_ref(), &indexed, &record("F2", "T1", 1)).await?,
+            None
+        );
 
-    async fn setup_schema(db: &Pool<Postgres>, s: &str) -> Result<()> {
-        sqlx::query(&format!("CREATE SCHEMA IF NOT EXISTS {s}"))
-            .execute(db)
-            .await?;
-        sqlx::query(&format!(
-            "CREATE TABLE IF NOT EXISTS {s}.sync_status (height BIGINT PRIMARY KEY)"
-        ))
-        .execute(db)
-        .await?;
-        sqlx::query(&format!("CREATE TABLE IF NOT EXISTS {s}.bind_info (from_addr TEXT, to_addr TEXT, timestamp BIGINT, height BIGINT, tx_index INTEGER, UNIQUE(from_addr, to_addr, timestamp))")).execute(db).await?;
-        sqlx::query(&format!("TRUNCATE TABLE {s}.bind_info RESTART IDENTITY"))
-            .execute(db)
-            .await?;
-        sqlx::query(&format!("TRUNCATE TABLE {s}.sync_status RESTART IDENTITY"))
-            .execute(db)
+        // a v1 BindInfo does not name its from, another signer may bind the same one
+        let other_signer = BindRecord {
+            from: "F3".to_string(),
+            ..record("F1", "T1", 10)
+        };
+        assert_eq!(
+            check_replay(storage.as_ref(), &indexed, &other_signer).await?,
+            None
+        );
+        let in_block = BindRecord {
+            from: "F3".to_string(),
+            ..record("F1", "T2", 20)
+        };
+        assert_eq!(
+            check_replay(storage.as_ref(), &indexed, &in_block).await?,
+            None
+        );
+        storage
+            .commit_block(&block(101, vec![(other_signer.clone(), 2)]))
             .await?;
+        let replayed = check_replay(storage.as_ref(), &block(102, vec![]), &other_signer).await?;
+        assert!(matches!(replayed, Some((RejectReason::Replayed, _))));
         Ok(())
     }
 
-    async fn seed_data(db: &Pool<Postgres>, s: &str) -> Result<()> {
-        // F1 -> T1 with multiple heights and tx_index
-        db.execute(query(&format!("INSERT INTO {s}.bind_info (from_addr, to_addr, timestamp, height, tx_index) VALUES ($1, $2, $3, $4, $5)"))
-            .bind("F1").bind("T1").bind(0_i64).bind(101_i64).bind(3_i32)).await?;
-        db.execute(query(&format!("INSERT INTO {s}.bind_info (from_addr, to_addr, timestamp, height, tx_index) VALUES ($1, $2, $3, $4, $5)"))
-            .bind("F1").bind("T1").bind(1_i64).bind(103_i64).bind(1_i32)).await?;
-        db.execute(query(&format!("INSERT INTO {s}.bind_info (from_addr, to_addr, timestamp, height, tx_index) VALUES ($1, $2, $3, $4, $5)"))
-            .bind("F1").bind("T1").bind(2_i64).bind(103_i64).bind(5_i32)).await?; // latest by height then tx_index
-        db.execute(query(&format!("INSERT INTO {s}.bind_info (from_addr, to_addr, timestamp, height, tx_index) VALUES ($1, $2, $3, $4, $5)"))
-            .bind("F1").bind("T1").bind(3_i64).bind(102_i64).bind(9_i32)).await?;
-
-        // F2 -> T1
-        db.execute(query(&format!("INSERT INTO {s}.bind_info (from_addr, to_addr, timestamp, height, tx_index) VALUES ($1, $2, $3, $4, $5)"))
-            .bind("F2").bind("T1").bind(4_i64).bind(88_i64).bind(7_i32)).await?;
-        db.execute(query(&format!("INSERT INTO {s}.bind_info (from_addr, to_addr, timestamp, height, tx_index) VALUES ($1, $2, $3, $4, $5)"))
-            .bind("F2").bind("T1").bind(5_i64).bind(100_i64).bind(2_i32)).await?;
-        db.execute(query(&format!("INSERT INTO {s}.bind_info (from_addr, to_addr, timestamp, height, tx_index) VALUES ($1, $2, $3, $4, $5)"))
-            .bind("F2").bind("T1").bind(6_i64).bind(100_i64).bind(6_i32)).await?; // latest by height then tx_index
+    #[test]
+    fn test_cursor() {
+        let cursor = Cursor {
+            at_height: 104,
+            height: 102,
+            tx_index: 9,
+        };
+        let decoded = Cursor::decode(&cursor.encode()).unwrap();
+        assert_eq!(
+            (decoded.at_height, decoded.height, decoded.tx_index),
+            (104, 102, 9)
+        );
+        assert!(Cursor::decode("not a cursor").is_err());
+    }
 
-        Ok(())
+    // block at height on top of parent_hash, with a cellbase and txs, inserted into chain
+    fn insert_block(
+        chain: &mut MemoryChain,
+        height: u64,
+        parent_hash: packed::Byte32,
+        txs: Vec<ckb_jsonrpc_types::Transaction>,
+    ) -> packed::Byte32 {
+        let block = BlockBuilder::default()
+            .number(height.pack())
+            .parent_hash(parent_hash)
+            .timestamp(TIMESTAMP.pack())
+            .epoch(
+                EpochNumberWithFraction::new(0, height, 1000)
+                    .full_value()
+                    .pack(),
+            )
+            .transaction(TransactionBuilder::default().build())
+            .transactions(
+                txs.into_iter()
+                    .map(|tx| packed::Transaction::from(tx).into_view()),
+            )
+            .build();
+        let hash = block.hash();
+        chain.insert_block(block.into());
+        hash
     }
 
     #[tokio::test]
-    async fn test_query_by_to_distinct_on() -> Result<()> {
-        let Some(db) = get_test_db().await else {
-            eprintln!("Skipped test_query_by_to_distinct_on: TEST_DB_URL/DB_URL not set");
-            return Ok(());
-        };
-        let s = test_schema("to");
-        setup_schema(&db, &s).await?;
-        seed_data(&db, &s).await?;
-
-        let sql = format!(
-            "SELECT DISTINCT ON (from_addr) from_addr, height, tx_index
-                           FROM {s}.bind_info
-                           WHERE to_addr = $1
-                           ORDER BY from_addr, height DESC, tx_index DESC"
+    async fn test_index_block() -> Result<()> {
+        let storage = storage::connect("sqlite::memory:").await?;
+        storage.migrate().await?;
+        let options = VerifyOptions::default();
+
+        let mut chain = MemoryChain::new();
+        let from_sk = SecretKey::from_slice(&[0x11; 32]).unwrap();
+        let to_lock = lock_of(&SecretKey::from_slice(&[0x22; 32]).unwrap());
+        let tx = bind_tx(&mut chain, &from_sk, &to_lock, &to_lock);
+        let hash = insert_block(&mut chain, 1, packed::Byte32::zero(), vec![tx]);
+
+        let network = NetworkType::Testnet;
+        assert_eq!(
+            index_block(&chain, storage.as_ref(), network, &options, 1).await?,
+            2
+        );
+        // not produced yet
+        assert_eq!(
+            index_block(&chain, storage.as_ref(), network, &options, 2).await?,
+            2
         );
-        let rows: Vec<(String, i64, i32)> = query_as(&sql).bind("T1").fetch_all(&db).await?;
 
-        // Expect F1->height 103, tx_index=5, F2->height 100, tx_index=6
-        let mut map = std::collections::HashMap::new();
-        for (from, height, tx_index) in rows {
-            map.insert(from, (height, tx_index));
-        }
-        assert_eq!(map.get("F1"), Some(&(103_i64, 5_i32)));
-        assert_eq!(map.get("F2"), Some(&(100_i64, 6_i32)));
+        let from = calculate_address(&lock_of(&from_sk), network).to_string();
+        let binding = storage.current_binding(&from, i64::MAX).await?.unwrap();
+        assert_eq!(
+            binding.to_addr,
+            calculate_address(&to_lock, network).to_string()
+        );
+        assert_eq!(binding.block_hash, Some(format!("{hash:#x}")));
+        assert_eq!(storage.sync_height().await?, Some(1));
         Ok(())
     }
 
     #[tokio::test]
-    async fn test_query_by_from_ordering() -> Result<()> {
-        let Some(db) = get_test_db().await else {
-            eprintln!("Skipped test_query_by_from_ordering: TEST_DB_URL/DB_URL not set");
-            return Ok(());
+    async fn test_index_block_reorg() -> Result<()> {
+        let storage = storage::connect("sqlite::memory:").await?;
+        storage.migrate().await?;
+        let options = VerifyOptions::default();
+        let network = NetworkType::Testnet;
+        let address = |sk: u8| {
+            let lock = lock_of(&SecretKey::from_slice(&[sk; 32]).unwrap());
+            (lock.clone(), calculate_address(&lock, network).to_string())
         };
-        let s = test_schema("from");
-        setup_schema(&db, &s).await?;
-        seed_data(&db, &s).await?;
-
-        let sql = format!(
-            "SELECT to_addr, height, tx_index
-                           FROM {s}.bind_info
-                           WHERE from_addr = $1
-                           ORDER BY height DESC, tx_index DESC"
+        let (to_lock1, to1) = address(0x22);
+        let (to_lock2, to2) = address(0x44);
+        let (to_lock3, to3) = address(0x55);
+        let from_sk = SecretKey::from_slice(&[0x33; 32]).unwrap();
+        let (_, from) = address(0x33);
+
+        // 1 <- 2 <- 3 indexed, then 2 and 3 replaced by a fork 2' <- 3' <- 4'
+        let mut chain = MemoryChain::new();
+        let other_sk = SecretKey::from_slice(&[0x11; 32]).unwrap();
+        let tx = bind_tx(&mut chain, &other_sk, &to_lock1, &to_lock1);
+        let hash1 = insert_block(&mut chain, 1, packed::Byte32::zero(), vec![tx]);
+        let tx = bind_tx(&mut chain, &from_sk, &to_lock2, &to_lock2);
+        let hash2 = insert_block(&mut chain, 2, hash1.clone(), vec![tx]);
+        insert_block(&mut chain, 3, hash2, vec![]);
+        for height in 1..=3 {
+            assert_eq!(
+                index_block(&chain, storage.as_ref(), network, &options, height).await?,
+                height + 1
+            );
+        }
+        assert_eq!(
+            storage
+                .current_binding(&from, i64::MAX)
+                .await?
+                .unwrap()
+                .to_addr,
+            to2
+        );
+
+        let tx = bind_tx(&mut chain, &from_sk, &to_lock3, &to_lock3);
+        let fork2 = insert_block(&mut chain, 2, hash1, vec![tx]);
+        let fork3 = insert_block(&mut chain, 3, fork2.clone(), vec![]);
+        let fork4 = insert_block(&mut chain, 4, fork3, vec![]);
+
+        // rolled back to the common ancestor, then the fork indexed from above it
+        assert_eq!(
+            index_block(&chain, storage.as_ref(), network, &options, 4).await?,
+            2
+        );
+        assert_eq!(storage.sync_height().await?, Some(1));
+        assert!(storage.current_binding(&from, i64::MAX).await?.is_none());
+        for height in 2..=4 {
+            assert_eq!(
+                index_block(&chain, storage.as_ref(), network, &options, height).await?,
+                height + 1
+            );
+        }
+
+        let binding = storage.current_binding(&from, i64::MAX).await?.unwrap();
+        assert_eq!(binding.to_addr, to3);
+        assert_eq!(binding.block_hash, Some(format!("{fork2:#x}")));
+        assert_eq!(
+            storage
+                .bindings_by_from(&from, &Page::at(i64::MAX))
+                .await?
+                .len(),
+            1
+        );
+        assert!(
+            storage
+                .bindings_by_to(&to2, &Page::at(i64::MAX))
+                .await?
+                .is_empty()
+        );
+        assert_eq!(
+            storage
+                .bindings_by_to(&to1, &Page::at(i64::MAX))
+                .await?
+                .len(),
+            1
+        );
+        assert_eq!(storage.sync_height().await?, Some(4));
+        assert_eq!(
+            storage.last_block().await?,
+            Some((4, Some(format!("{fork4:#x}"))))
         );
-        let rows: Vec<(String, i64, i32)> = query_as(&sql).bind("F1").fetch_all(&db).await?;
-
-        // First row should be height=103, tx_index=5
-        assert!(!rows.is_empty());
-        let (to, height, tx_index) = &rows[0];
-        assert_eq!(to, "T1");
-        assert_eq!(*height, 103_i64);
-        assert_eq!(*tx_index, 5_i32);
         Ok(())
     }
 
     #[tokio::test]
-    async fn test_query_by_to_at_height_cutoff() -> Result<()> {
-        let Some(db) = get_test_db().await else {
-            eprintln!("Skipped test_query_by_to_at_height_cutoff: TEST_DB_URL/DB_URL not set");
-            return Ok(());
+    async fn test_pending_until_confirmed() -> Result<()> {
+        let storage = storage::connect("sqlite::memory:").await?;
+        storage.migrate().await?;
+        let network = NetworkType::Testnet;
+        let state = Indexer {
+            storage: storage.clone(),
+            tip_height: Arc::new(AtomicU64::new(0)),
+            confirmations: 2,
+            recovery_url: None,
+            network,
         };
-        let s = test_schema("toheight");
-        setup_schema(&db, &s).await?;
-        seed_data(&db, &s).await?;
-
-        // Same data as before; cut off at height <= 100
-        let sql = format!(
-            "SELECT DISTINCT ON (from_addr) from_addr, height, tx_index
-                         FROM {s}.bind_info
-                         WHERE to_addr = $1 AND height <= $2
-                         ORDER BY from_addr, height DESC, tx_index DESC"
-        );
-        let rows: Vec<(String, i64, i32)> = query_as(&sql)
-            .bind("T1")
-            .bind(100_i64)
-            .fetch_all(&db)
-            .await?;
 
-        // Expect F1 excluded (latest at 103), F2 included with height 100, tx_index=6
-        assert_eq!(rows.len(), 1);
-        assert_eq!(rows[0].0, "F2");
-        assert_eq!(rows[0].1, 100_i64);
-        assert_eq!(rows[0].2, 6_i32);
+        let mut chain = MemoryChain::new();
+        let from_sk = SecretKey::from_slice(&[0x11; 32]).unwrap();
+        let to_lock = lock_of(&SecretKey::from_slice(&[0x22; 32]).unwrap());
+        let tx = bind_tx(&mut chain, &from_sk, &to_lock, &to_lock);
+        insert_block(&mut chain, 1, packed::Byte32::zero(), vec![tx]);
+        index_block(
+            &chain,
+            storage.as_ref(),
+            network,
+            &VerifyOptions::default(),
+            1,
+        )
+        .await?;
+        let from = calculate_address(&lock_of(&from_sk), network).to_string();
+
+        // status and final_only follow the tip as blocks are added on top
+        let final_only = BindingParams {
+            final_only: true,
+            ..Default::default()
+        };
+        let current = |max_height| storage.current_binding(&from, max_height);
+        for (tip, status) in [(1, "pending"), (2, "pending"), (3, "final")] {
+            state.tip_height.store(tip, Ordering::Relaxed);
+            let binding = current(i64::MAX).await?.unwrap();
+            assert_eq!(binding.to_json(&state)["status"], status);
+            assert_eq!(binding.to_json(&state)["confirmations"], tip - 1);
+            let final_binding = current(state.max_height(&final_only).await.unwrap()).await?;
+            assert_eq!(final_binding.is_some(), status == "final");
+        }
         Ok(())
     }
 }
@@ -1,15 +1,35 @@
 mod bind;
+mod chain;
 mod error;
 mod indexer;
+mod message;
+mod send;
+mod signature;
+mod storage;
 mod verify;
 
 #[macro_use]
 extern crate tracing as logger;
 
-use ckb_sdk::NetworkType;
+use chain::{ChainSource, FixtureChain, TxBundle};
 use ckb_sdk::rpc::CkbRpcClient;
-use ckb_types::H256;
-use clap::{Parser, Subcommand};
+use ckb_sdk::{
+    Address, NetworkType, ScriptId,
+    constants::{MultisigScript, SIGHASH_TYPE_HASH},
+    unlock::MultisigConfig,
+};
+use ckb_types::{H160, H256, packed, prelude::Unpack};
+use clap::{Parser, Subcommand, ValueEnum};
+use color_eyre::eyre::eyre;
+use error::VerifyError;
+use molecule::prelude::Entity;
+use secp256k1::SecretKey;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::sync::atomic::AtomicU64;
+use std::time::{SystemTime, UNIX_EPOCH};
+use verify::{BindRecord, ShapePolicy, VerifyOptions};
 
 #[derive(Parser, Debug, Clone)]
 #[command(author, version, about, long_about = None)]
@@ -18,83 +38,559 @@ struct Cli {
     ckb_url: String,
     #[arg(short, long, default_value = "ckb_testnet")]
     network: String,
-    #[arg(short, long, default_value = "http://localhost:3000")]
-    recovery_url: String,
+    /// optional recoverysig service, only used for signatures can not be verified natively
+    #[arg(short, long)]
+    recovery_url: Option<String>,
+    /// only accept bind txs of exactly one input and one output
+    #[arg(long)]
+    strict_shape: bool,
+    /// read chain data from a json fixture directory instead of --ckb-url
+    #[arg(long)]
+    fixtures: Option<String>,
+    /// application domain of v2 bind infos, signed by bind-info and checked by verify and indexer
+    #[arg(long, default_value = message::DEFAULT_DOMAIN)]
+    domain: String,
     #[command(subcommand)]
     command: Commands,
 }
 
 #[derive(Subcommand, Debug, Clone)]
 enum Commands {
+    /// exit code is 0 if all txs are valid, 1 if any is invalid,
+    /// 2 on bad arguments or input files, 3 if any could not be checked (rpc, recovery service)
     Verify {
+        /// may be repeated
+        #[arg(short, long, required_unless_present_any = ["tx_file", "tx_hashes_file"])]
+        tx_hash: Vec<String>,
+        /// file with one tx hash per line, blank lines and lines starting with # are ignored
+        #[arg(long)]
+        tx_hashes_file: Option<PathBuf>,
+        /// verify a transaction from a json file without network,
+        /// a Transaction, a TransactionView or a bundle with its prev outputs
+        #[arg(long, conflicts_with_all = ["tx_hash", "tx_hashes_file"])]
+        tx_file: Option<PathBuf>,
+        /// json list of {out_point, output} spent by --tx-file
+        #[arg(long, requires = "tx_file")]
+        prev_outputs: Option<PathBuf>,
+        #[arg(short, long, value_enum, default_value_t = OutputFormat::Text)]
+        output: OutputFormat,
+    },
+    /// print the 0x-hex BindInfo message to be signed by the from key
+    BindInfo {
+        /// address of the to lock, the bind tx transfers to it
+        #[arg(long)]
+        to: String,
+        /// address of the from lock, prints a v2 BindInfo for --network and --domain
+        #[arg(long)]
+        from: Option<String>,
+        /// milliseconds since epoch, defaults to now
+        #[arg(long)]
+        timestamp: Option<u64>,
+    },
+    /// print the 0x-hex BindInfoWithSig, put into input_type of the bind tx witness
+    Compose {
+        /// 0x-hex BindInfo from bind-info
+        #[arg(long)]
+        bind_info: String,
+        /// 0x-hex 65 bytes Neuron signature, or the json of a ccc Signature Object.
+        /// with --multisig-member, repeated once per signing member
+        #[arg(long, required = true)]
+        sig: Vec<String>,
+        /// secp256k1 address of a member of the from multisig, in the multisig order, may be repeated
+        #[arg(long, requires = "threshold")]
+        multisig_member: Vec<String>,
+        /// number of member signatures the multisig requires
+        #[arg(long, requires = "multisig_member")]
+        threshold: Option<u8>,
+        /// the first n members must sign
+        #[arg(long, default_value = "0")]
+        require_first_n: u8,
+        /// since of a time-locked multisig address, as in its lock args
+        #[arg(long, requires = "multisig_member")]
+        since: Option<u64>,
+        /// the from is a multisig v2 address instead of a legacy multisig address
+        #[arg(long, requires = "multisig_member")]
+        multisig_v2: bool,
+        /// print the whole WitnessArgs instead
+        #[arg(long)]
+        witness_args: bool,
+    },
+    /// sign a message such as a BindInfo from bind-info like neuron, prints the 0x-hex 65 bytes signature
+    SignMessage {
+        /// the message as shown to the signer, the 0x-hex BindInfo
+        #[arg(long)]
+        message: String,
+        /// 0x-hex secp256k1 private key of the from lock
+        #[arg(
+            long,
+            required_unless_present = "key_file",
+            conflicts_with = "key_file"
+        )]
+        key: Option<String>,
+        /// file containing the 0x-hex private key
+        #[arg(long)]
+        key_file: Option<PathBuf>,
+    },
+    /// build, sign and send the bind tx, spending a cell of the to key back to itself
+    SendBind {
+        /// 0x-hex secp256k1 private key of the to lock
+        #[arg(
+            long,
+            required_unless_present = "to_key_file",
+            conflicts_with = "to_key_file"
+        )]
+        to_key: Option<String>,
+        /// file containing the 0x-hex private key
+        #[arg(long)]
+        to_key_file: Option<PathBuf>,
+        /// 0x-hex BindInfoWithSig from compose
+        #[arg(long)]
+        bind_info_with_sig: String,
+        /// shannons per 1000 bytes
+        #[arg(long, default_value = "1000")]
+        fee_rate: u64,
+    },
+    /// apply database schema migrations and exit
+    Migrate {
         #[arg(short, long)]
-        tx_hash: String,
+        db_url: String,
     },
     Indexer {
         #[arg(short, long, default_value = "info")]
         log_filter: String,
-        #[arg(short, long, default_value = "18_587_462")]
+        #[arg(short, long, default_value = "18587462")]
         start_height: u64,
         #[arg(short, long)]
         db_url: String,
         #[arg(short, long, default_value = "9533")]
         port: u16,
+        /// blocks on top of a binding before it is reported as final
+        #[arg(long, default_value = "0")]
+        confirmations: u64,
     },
 }
 
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum OutputFormat {
+    Text,
+    /// one json object per tx and line
+    Json,
+}
+
+// exit codes of verify, 2 is what clap uses for bad arguments
+const EXIT_INVALID: i32 = 1;
+const EXIT_BAD_INPUT: i32 = 2;
+const EXIT_TRANSIENT: i32 = 3;
+
 #[derive(Debug, Clone)]
 pub struct Indexer {
-    pub db: sqlx::Pool<sqlx::Postgres>,
+    pub storage: Arc<dyn storage::Storage>,
+    pub tip_height: Arc<AtomicU64>,
+    pub confirmations: u64,
+    pub recovery_url: Option<String>,
+    // addresses in queries are normalized to this network
+    pub network: NetworkType,
 }
 
-#[tokio::main]
-async fn main() {
-    let cli = Cli::parse();
+// Signature Object is passed as json, raw signature as hex
+fn parse_sig(sig: &str) -> Result<Vec<u8>, String> {
+    if sig.trim_start().starts_with('{') {
+        Ok(sig.as_bytes().to_vec())
+    } else {
+        hex::decode(sig.trim_start_matches("0x")).map_err(|e| format!("parse sig hex failed: {e}"))
+    }
+}
 
-    let network_type =
-        NetworkType::from_raw_str(&cli.network).expect("network must be 'ckb' or 'ckb_testnet'");
+// sig of a sighash_all multisig from its members and their neuron signatures
+fn multisig_sig(
+    multisig: MultisigScript,
+    members: &[String],
+    threshold: u8,
+    require_first_n: u8,
+    since: Option<u64>,
+    sigs: &[String],
+) -> Result<Vec<u8>, String> {
+    let pubkey_hashes = members
+        .iter()
+        .map(|member| {
+            let address = Address::from_str(member)
+                .map_err(|e| format!("parse address {member} failed: {e}"))?;
+            let lock = packed::Script::from(address.payload());
+            if ScriptId::from(&lock) != ScriptId::new_type(SIGHASH_TYPE_HASH) {
+                return Err(format!("{member} is not a secp256k1 address"));
+            }
+            H160::from_slice(&lock.args().raw_data())
+                .map_err(|_| format!("{member} is not a secp256k1 address"))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let config = MultisigConfig::new_with(multisig, pubkey_hashes, require_first_n, threshold)
+        .map_err(|e| e.to_string())?;
+    if sigs.len() != threshold as usize {
+        return Err(format!("need {threshold} --sig, got {}", sigs.len()));
+    }
+    let sigs = sigs
+        .iter()
+        .map(|sig| parse_sig(sig))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(message::multisig_sig(
+        multisig,
+        &config.to_witness_data(),
+        since,
+        &sigs,
+    ))
+}
 
-    match &cli.command {
-        Commands::Verify { tx_hash } => {
-            let ckb_client = CkbRpcClient::new(cli.ckb_url.as_str());
+fn verify_options(cli: &Cli) -> VerifyOptions {
+    VerifyOptions {
+        recovery_url: cli.recovery_url.clone(),
+        shape: if cli.strict_shape {
+            ShapePolicy::Strict
+        } else {
+            ShapePolicy::LockGroup
+        },
+        domain: cli.domain.clone(),
+    }
+}
+
+fn chain_source(cli: &Cli) -> Box<dyn ChainSource> {
+    match &cli.fixtures {
+        Some(dir) => Box::new(FixtureChain::new(dir)),
+        None => Box::new(CkbRpcClient::new(cli.ckb_url.as_str())),
+    }
+}
 
-            let mut tx_hash = tx_hash.clone();
-            if tx_hash.starts_with("0x") {
-                tx_hash = tx_hash[2..].to_string();
+// private key given inline or in a file, 0x-hex
+fn read_private_key(key: Option<&str>, key_file: Option<&Path>) -> color_eyre::Result<SecretKey> {
+    let key = match (key, key_file) {
+        (Some(key), _) => key.to_string(),
+        (None, Some(path)) => std::fs::read_to_string(path)
+            .map_err(|e| eyre!("read {} failed: {e}", path.display()))?,
+        (None, None) => return Err(eyre!("private key is required")),
+    };
+    hex::decode(key.trim().trim_start_matches("0x"))
+        .ok()
+        .and_then(|key| SecretKey::from_slice(&key).ok())
+        .ok_or_else(|| eyre!("malformed private key"))
+}
+
+fn parse_tx_hash(tx_hash: &str) -> Result<H256, String> {
+    let bytes = hex::decode(tx_hash.trim_start_matches("0x"))
+        .map_err(|e| format!("malformed tx hash {tx_hash}: {e}"))?;
+    H256::from_slice(&bytes).map_err(|e| format!("malformed tx hash {tx_hash}: {e}"))
+}
+
+fn read_tx_hashes(content: &str) -> Vec<String> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect()
+}
+
+// invalid outranks transient, a retry will not make it valid
+fn exit_code(results: &[(H256, Result<BindRecord, VerifyError>)]) -> i32 {
+    let failed = |f: fn(&VerifyError) -> bool| {
+        results
+            .iter()
+            .any(|(_, ret)| ret.as_ref().err().is_some_and(f))
+    };
+    if failed(|e| !matches!(e, VerifyError::Transient(_))) {
+        EXIT_INVALID
+    } else if failed(|e| matches!(e, VerifyError::Transient(_))) {
+        EXIT_TRANSIENT
+    } else {
+        0
+    }
+}
+
+fn result_json(tx_hash: &H256, ret: &Result<BindRecord, VerifyError>) -> serde_json::Value {
+    match ret {
+        Ok(record) => serde_json::json!({
+            "tx_hash": format!("{tx_hash:#x}"),
+            "status": "valid",
+            "from": record.from,
+            "to": record.to,
+            "from_lock_hash": format!("{:#x}", record.from_lock.calc_script_hash()),
+            "to_lock_hash": format!("{:#x}", record.to_lock.calc_script_hash()),
+            "timestamp": record.timestamp,
+            "sig_scheme": record.sig_scheme,
+            "version": record.version,
+            "reason": null,
+            "error": null,
+        }),
+        Err(e) => serde_json::json!({
+            "tx_hash": format!("{tx_hash:#x}"),
+            "status": if matches!(e, VerifyError::Transient(_)) { "transient" } else { "invalid" },
+            "from": null,
+            "to": null,
+            "from_lock_hash": null,
+            "to_lock_hash": null,
+            "timestamp": null,
+            "sig_scheme": null,
+            "version": null,
+            "reason": e.reason(),
+            "error": e.to_string(),
+        }),
+    }
+}
+
+async fn verify_command(
+    cli: &Cli,
+    network_type: NetworkType,
+    tx_hashes: &[String],
+    tx_hashes_file: Option<&Path>,
+    tx_file: Option<&Path>,
+    prev_outputs: Option<&Path>,
+    output: OutputFormat,
+) -> i32 {
+    let mut results = vec![];
+    if let Some(tx_file) = tx_file {
+        // offline, chain data comes only from the files
+        let bundle = match TxBundle::load(tx_file, prev_outputs) {
+            Ok(bundle) => bundle,
+            Err(e) => {
+                eprintln!("{e}");
+                return EXIT_BAD_INPUT;
             }
-            let tx_hash_bytes = hex::decode(tx_hash).unwrap();
-            let tx_hash = H256::from_slice(&tx_hash_bytes).unwrap();
-            let tx = verify::get_tx(&ckb_client, tx_hash.clone()).await.unwrap();
+        };
+        let tx_hash: H256 = packed::Transaction::from(bundle.transaction.clone())
+            .calc_tx_hash()
+            .unpack();
+        let ret = verify::verify_tx(
+            &bundle.chain(),
+            network_type,
+            &bundle.transaction,
+            &verify_options(cli),
+        )
+        .await;
+        results.push((tx_hash, ret));
+    } else {
+        let mut tx_hashes = tx_hashes.to_vec();
+        if let Some(path) = tx_hashes_file {
+            match std::fs::read_to_string(path) {
+                Ok(content) => tx_hashes.extend(read_tx_hashes(&content)),
+                Err(e) => {
+                    eprintln!("read {} failed: {e}", path.display());
+                    return EXIT_BAD_INPUT;
+                }
+            }
+        }
+        // check all hashes before touching the chain
+        let tx_hashes = match tx_hashes
+            .iter()
+            .map(|tx_hash| parse_tx_hash(tx_hash))
+            .collect::<Result<Vec<_>, _>>()
+        {
+            Ok(tx_hashes) => tx_hashes,
+            Err(e) => {
+                eprintln!("{e}");
+                return EXIT_BAD_INPUT;
+            }
+        };
 
-            let ret =
-                verify::verify_tx(&ckb_client, network_type, &tx, cli.recovery_url.as_str()).await;
-            match ret {
-                Ok((from, to, timestamp)) => {
+        let chain = chain_source(cli);
+        for tx_hash in tx_hashes {
+            let ret = match verify::get_tx(chain.as_ref(), tx_hash.clone()).await {
+                Ok(tx) => {
+                    verify::verify_tx(chain.as_ref(), network_type, &tx, &verify_options(cli)).await
+                }
+                Err(e) => Err(e),
+            };
+            results.push((tx_hash, ret));
+        }
+    }
+
+    for (tx_hash, ret) in &results {
+        match output {
+            OutputFormat::Json => println!("{}", result_json(tx_hash, ret)),
+            OutputFormat::Text => match ret {
+                Ok(record) => {
                     println!(
-                        "tx {tx_hash} has valid bind info, from: {from}, to: {to}, timestamp: {timestamp}"
+                        "tx {tx_hash} has valid bind info, from: {}, to: {}, timestamp: {}, sig_scheme: {}",
+                        record.from, record.to, record.timestamp, record.sig_scheme
                     );
                 }
                 Err(e) => {
                     println!("tx {tx_hash} is invalid, err: {e}");
                 }
+            },
+        }
+    }
+    exit_code(&results)
+}
+
+#[tokio::main]
+async fn main() {
+    let cli = Cli::parse();
+
+    let Some(network_type) = NetworkType::from_raw_str(&cli.network) else {
+        eprintln!("network must be 'ckb' or 'ckb_testnet'");
+        std::process::exit(EXIT_BAD_INPUT);
+    };
+
+    match &cli.command {
+        Commands::Verify {
+            tx_hash,
+            tx_hashes_file,
+            tx_file,
+            prev_outputs,
+            output,
+        } => {
+            let code = verify_command(
+                &cli,
+                network_type,
+                tx_hash,
+                tx_hashes_file.as_deref(),
+                tx_file.as_deref(),
+                prev_outputs.as_deref(),
+                *output,
+            )
+            .await;
+            std::process::exit(code);
+        }
+        Commands::BindInfo {
+            to,
+            from,
+            timestamp,
+        } => {
+            let parse_lock = |address: &str| match Address::from_str(address) {
+                Ok(address) => packed::Script::from(address.payload()),
+                Err(e) => {
+                    eprintln!("parse address {address} failed: {e}");
+                    std::process::exit(EXIT_BAD_INPUT);
+                }
+            };
+            let to = parse_lock(to);
+            let timestamp = timestamp.unwrap_or_else(|| {
+                SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |elapsed| elapsed.as_millis() as u64)
+            });
+            let bind_info = match from {
+                Some(from) => message::build_bind_info_v2(
+                    &parse_lock(from),
+                    &to,
+                    timestamp,
+                    network_type,
+                    &cli.domain,
+                )
+                .as_bytes(),
+                None => message::build_bind_info(&to, timestamp).as_bytes(),
+            };
+            println!("{}", message::bind_info_message(&bind_info));
+        }
+        Commands::Compose {
+            bind_info,
+            sig,
+            multisig_member,
+            threshold,
+            require_first_n,
+            since,
+            multisig_v2,
+            witness_args,
+        } => {
+            let ret = hex::decode(bind_info.trim_start_matches("0x"))
+                .map_err(|e| format!("parse bind info hex failed: {e}"))
+                .and_then(|bind_info| {
+                    let sig = match threshold {
+                        Some(threshold) => multisig_sig(
+                            if *multisig_v2 {
+                                MultisigScript::V2
+                            } else {
+                                MultisigScript::Legacy
+                            },
+                            multisig_member,
+                            *threshold,
+                            *require_first_n,
+                            *since,
+                            sig,
+                        )?,
+                        None if sig.len() == 1 => parse_sig(&sig[0])?,
+                        None => return Err("multiple --sig need --multisig-member".to_string()),
+                    };
+                    message::compose(&bind_info, &sig)
+                });
+            match ret {
+                Ok(bind_info_with_sig) if *witness_args => {
+                    let witness = message::witness_args(&bind_info_with_sig);
+                    println!("0x{}", hex::encode(witness.as_slice()));
+                }
+                Ok(bind_info_with_sig) => {
+                    println!("0x{}", hex::encode(bind_info_with_sig.as_slice()));
+                }
+                Err(e) => {
+                    eprintln!("{e}");
+                    std::process::exit(EXIT_BAD_INPUT);
+                }
+            }
+        }
+        Commands::SignMessage {
+            message,
+            key,
+            key_file,
+        } => match read_private_key(key.as_deref(), key_file.as_deref()) {
+            Ok(key) => {
+                let sig = signature::neuron_sign_message(message, &key);
+                println!("0x{}", hex::encode(sig));
+            }
+            Err(e) => {
+                eprintln!("{e}");
+                std::process::exit(EXIT_BAD_INPUT);
+            }
+        },
+        Commands::SendBind {
+            to_key,
+            to_key_file,
+            bind_info_with_sig,
+            fee_rate,
+        } => {
+            let ret = async {
+                let to_key = read_private_key(to_key.as_deref(), to_key_file.as_deref())?;
+                let bind_info_with_sig =
+                    hex::decode(bind_info_with_sig.trim_start_matches("0x"))
+                        .map_err(|e| eyre!("parse BindInfoWithSig hex failed: {e}"))?;
+                let bind_info_with_sig =
+                    message::SignedBindInfo::parse(&bind_info_with_sig).map_err(|e| eyre!(e))?;
+                let ckb_client = CkbRpcClient::new(cli.ckb_url.as_str());
+                send::send_bind(&ckb_client, &to_key, &bind_info_with_sig, *fee_rate).await
+            }
+            .await;
+            match ret {
+                Ok(tx_hash) => println!("{tx_hash:#x}"),
+                Err(e) => {
+                    eprintln!("send bind tx failed: {e}");
+                    std::process::exit(EXIT_INVALID);
+                }
             }
         }
+        Commands::Migrate { db_url } => {
+            common_x::log::init_log_filter("info");
+            let ret = async { storage::connect(db_url).await?.migrate().await }.await;
+            if let Err(e) = ret {
+                error!("migrate error: {e}");
+                std::process::exit(1);
+            }
+            info!("database is up to date");
+        }
         Commands::Indexer {
             log_filter,
             start_height,
             port,
             db_url,
+            confirmations,
         } => {
             common_x::log::init_log_filter(log_filter);
             info!("args: {:?}", cli);
-            let ckb_client = CkbRpcClient::new(cli.ckb_url.as_str());
+            let chain = chain_source(&cli);
             let ret = indexer::server(
-                &ckb_client,
+                chain.as_ref(),
                 network_type,
                 db_url,
                 *start_height,
                 *port,
-                cli.recovery_url.as_str(),
+                *confirmations,
+                verify_options(&cli),
             )
             .await;
             if let Err(e) = ret {
@@ -103,3 +599,66 @@ async fn main() {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ckb_types::prelude::{Builder, Pack};
+    use error::RejectReason;
+
+    #[test]
+    fn test_read_tx_hashes() {
+        let hashes = read_tx_hashes("# batch\n0x01\n\n  0x02  \n");
+        assert_eq!(hashes, vec!["0x01", "0x02"]);
+        assert!(parse_tx_hash(&format!("0x{}", "ab".repeat(32))).is_ok());
+        assert!(parse_tx_hash(&"ab".repeat(32)).is_ok());
+        assert!(parse_tx_hash("0x1234").is_err());
+        assert!(parse_tx_hash("xyz").is_err());
+    }
+
+    #[test]
+    fn test_exit_code() {
+        let transient = (
+            H256::default(),
+            Err(VerifyError::Transient("rpc".to_string())),
+        );
+        let invalid = (
+            H256::default(),
+            Err(VerifyError::Rejected(
+                RejectReason::InvalidSignature,
+                "sig".to_string(),
+            )),
+        );
+        assert_eq!(exit_code(&[]), 0);
+        assert_eq!(exit_code(std::slice::from_ref(&transient)), EXIT_TRANSIENT);
+        assert_eq!(exit_code(&[transient, invalid]), EXIT_INVALID);
+    }
+
+    #[test]
+    fn test_multisig_members() {
+        let lock = verify::tests::lock_of(&SecretKey::from_slice(&[1; 32]).unwrap());
+        let member = verify::calculate_address(&lock, NetworkType::Testnet).to_string();
+        let sig = format!("0x{}", "00".repeat(65));
+        let multisig_sig = |member: &str| {
+            multisig_sig(
+                MultisigScript::V2,
+                &[member.to_string()],
+                1,
+                0,
+                None,
+                std::slice::from_ref(&sig),
+            )
+        };
+        assert!(multisig_sig(&member).is_ok());
+        // 20 bytes args under another code hash is not a member key
+        let other = lock
+            .as_builder()
+            .code_hash(MultisigScript::V2.script_id().code_hash.pack())
+            .build();
+        let other = verify::calculate_address(&other, NetworkType::Testnet).to_string();
+        assert_eq!(
+            multisig_sig(&other),
+            Err(format!("{other} is not a secp256k1 address"))
+        );
+    }
+}
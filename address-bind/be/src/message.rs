@@ -0,0 +1,235 @@
+use crate::bind::{
+    BindInfo, BindInfoV2, BindInfoV2WithSig, BindInfoWithSig, Bytes, Script, Uint64,
+};
+use crate::signature::{is_multisig_sig, multisig_s, parse_multisig_sig, parse_signature_object};
+use ckb_sdk::{NetworkType, constants::MultisigScript};
+use ckb_types::{packed, prelude::Pack};
+use molecule::prelude::{Builder, Entity};
+
+// application domain of BindInfoV2 signed for this service
+pub const DEFAULT_DOMAIN: &str = "address-bind";
+
+// BindInfo binding the signer to the owner of lock `to`
+pub fn build_bind_info(to: &packed::Script, timestamp: u64) -> BindInfo {
+    BindInfo::new_builder()
+        .to(Script::new_unchecked(to.as_bytes()))
+        .timestamp(Uint64::from(timestamp.to_le_bytes()))
+        .build()
+}
+
+// BindInfoV2 binding lock `from` to lock `to`, only valid on network in domain
+pub fn build_bind_info_v2(
+    from: &packed::Script,
+    to: &packed::Script,
+    timestamp: u64,
+    network: NetworkType,
+    domain: &str,
+) -> BindInfoV2 {
+    let network: packed::Bytes = network.to_str().as_bytes().to_vec().pack();
+    let domain: packed::Bytes = domain.as_bytes().to_vec().pack();
+    BindInfoV2::new_builder()
+        .from(Script::new_unchecked(from.as_bytes()))
+        .to(Script::new_unchecked(to.as_bytes()))
+        .timestamp(Uint64::from(timestamp.to_le_bytes()))
+        .network(Bytes::new_unchecked(network.as_bytes()))
+        .domain(Bytes::new_unchecked(domain.as_bytes()))
+        .build()
+}
+
+// the message the from key signs, 0x-hex of the BindInfo bytes
+pub fn bind_info_message(bind_info: &[u8]) -> String {
+    format!("0x{}", hex::encode(bind_info))
+}
+
+// BindInfoWithSig of either version, as found in input_type of a bind tx
+#[derive(Debug, Clone)]
+pub enum SignedBindInfo {
+    V1(BindInfoWithSig),
+    V2(BindInfoV2WithSig),
+}
+
+impl SignedBindInfo {
+    // a v2 BindInfo has 5 fields, so it is never taken for v1 which may have extra fields
+    pub fn parse(bytes: &[u8]) -> Result<Self, String> {
+        if let Ok(v2) = BindInfoV2WithSig::from_slice(bytes) {
+            return Ok(SignedBindInfo::V2(v2));
+        }
+        BindInfoWithSig::from_compatible_slice(bytes)
+            .map(SignedBindInfo::V1)
+            .map_err(|e| format!("parse bind info with sig failed: {e}"))
+    }
+
+    pub const fn version(&self) -> u8 {
+        match self {
+            SignedBindInfo::V1(_) => 1,
+            SignedBindInfo::V2(_) => 2,
+        }
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        match self {
+            SignedBindInfo::V1(v1) => v1.as_slice(),
+            SignedBindInfo::V2(v2) => v2.as_slice(),
+        }
+    }
+
+    // the signed BindInfo bytes
+    pub fn bind_info(&self) -> Vec<u8> {
+        match self {
+            SignedBindInfo::V1(v1) => v1.bind_info().as_slice().to_vec(),
+            SignedBindInfo::V2(v2) => v2.bind_info().as_slice().to_vec(),
+        }
+    }
+
+    pub fn sig(&self) -> Vec<u8> {
+        match self {
+            SignedBindInfo::V1(v1) => v1.sig().raw_data().to_vec(),
+            SignedBindInfo::V2(v2) => v2.sig().raw_data().to_vec(),
+        }
+    }
+
+    pub fn to(&self) -> packed::Script {
+        let to = match self {
+            SignedBindInfo::V1(v1) => v1.bind_info().to(),
+            SignedBindInfo::V2(v2) => v2.bind_info().to(),
+        };
+        packed::Script::new_unchecked(to.as_bytes())
+    }
+
+    pub fn timestamp(&self) -> u64 {
+        let timestamp = match self {
+            SignedBindInfo::V1(v1) => v1.bind_info().timestamp(),
+            SignedBindInfo::V2(v2) => v2.bind_info().timestamp(),
+        };
+        u64::from_le_bytes(timestamp.into())
+    }
+}
+
+// sig of multisig members, see parse_multisig_sig
+pub fn multisig_sig(
+    multisig: MultisigScript,
+    multisig_script: &[u8],
+    since: Option<u64>,
+    sigs: &[Vec<u8>],
+) -> Vec<u8> {
+    let mut sig = multisig_script.to_vec();
+    sig[0] = multisig_s(multisig);
+    if let Some(since) = since {
+        sig.extend_from_slice(&since.to_le_bytes());
+    }
+    for member_sig in sigs {
+        sig.extend_from_slice(member_sig);
+    }
+    sig
+}
+
+// bind_info is a BindInfo or BindInfoV2, sig a 65 bytes Neuron signature, a multisig sig
+// or the json of a ccc Signature Object
+pub fn compose(bind_info: &[u8], sig: &[u8]) -> Result<SignedBindInfo, String> {
+    if sig.len() != 65 {
+        if is_multisig_sig(sig) {
+            parse_multisig_sig(sig)?;
+        } else {
+            parse_signature_object(sig)?;
+        }
+    }
+    let sig: packed::Bytes = sig.to_vec().pack();
+    let sig = Bytes::new_unchecked(sig.as_bytes());
+    if let Ok(bind_info) = BindInfoV2::from_slice(bind_info) {
+        return Ok(SignedBindInfo::V2(
+            BindInfoV2WithSig::new_builder()
+                .bind_info(bind_info)
+                .sig(sig)
+                .build(),
+        ));
+    }
+    let bind_info =
+        BindInfo::from_slice(bind_info).map_err(|e| format!("parse bind info failed: {e}"))?;
+    Ok(SignedBindInfo::V1(
+        BindInfoWithSig::new_builder()
+            .bind_info(bind_info)
+            .sig(sig)
+            .build(),
+    ))
+}
+
+// witness of the bind tx, BindInfoWithSig in input_type
+pub fn witness_args(bind_info_with_sig: &SignedBindInfo) -> packed::WitnessArgs {
+    let input_type: packed::Bytes = bind_info_with_sig.as_slice().to_vec().pack();
+    packed::WitnessArgs::new_builder()
+        .input_type(Some(input_type).pack())
+        .build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ckb_types::core::ScriptHashType;
+
+    #[test]
+    fn test_compose() {
+        let to = packed::Script::new_builder()
+            .code_hash([1u8; 32].pack())
+            .hash_type(ScriptHashType::Type.into())
+            .args([2u8; 20].as_slice().pack())
+            .build();
+        let bind_info = build_bind_info(&to, 1760432079687);
+        assert_eq!(bind_info.to().as_slice(), to.as_slice());
+        assert_eq!(
+            u64::from_le_bytes(bind_info.timestamp().into()),
+            1760432079687
+        );
+        assert!(bind_info_message(bind_info.as_slice()).starts_with("0x"));
+
+        let bind_info_with_sig = compose(bind_info.as_slice(), &[7u8; 65]).unwrap();
+        assert_eq!(bind_info_with_sig.bind_info(), bind_info.as_slice());
+        assert_eq!(bind_info_with_sig.sig(), [7u8; 65]);
+        assert_eq!(bind_info_with_sig.version(), 1);
+
+        let sig_obj = br#"{"signature":"0x00","identity":"0x00","signType":"EvmPersonal"}"#;
+        assert!(compose(bind_info.as_slice(), sig_obj).is_ok());
+        // neither a raw signature nor a Signature Object
+        assert!(compose(bind_info.as_slice(), &[7u8; 64]).is_err());
+        assert!(compose(&[0u8; 3], &[7u8; 65]).is_err());
+    }
+
+    #[test]
+    fn test_compose_v2() {
+        let script = |args: u8| {
+            packed::Script::new_builder()
+                .code_hash([1u8; 32].pack())
+                .hash_type(ScriptHashType::Type.into())
+                .args([args; 20].as_slice().pack())
+                .build()
+        };
+        let (from, to) = (script(2), script(3));
+        let bind_info = build_bind_info_v2(
+            &from,
+            &to,
+            1760432079687,
+            NetworkType::Testnet,
+            DEFAULT_DOMAIN,
+        );
+        assert_eq!(bind_info.from().as_slice(), from.as_slice());
+        assert_eq!(bind_info.network().raw_data().as_ref(), b"ckb_testnet");
+        assert_eq!(
+            bind_info.domain().raw_data().as_ref(),
+            DEFAULT_DOMAIN.as_bytes()
+        );
+
+        let bind_info_with_sig = compose(bind_info.as_slice(), &[7u8; 65]).unwrap();
+        assert_eq!(bind_info_with_sig.version(), 2);
+        assert_eq!(bind_info_with_sig.to(), to);
+        assert_eq!(bind_info_with_sig.timestamp(), 1760432079687);
+
+        // both versions parse back from input_type
+        let v2 = SignedBindInfo::parse(bind_info_with_sig.as_slice()).unwrap();
+        assert_eq!(v2.version(), 2);
+        assert_eq!(v2.bind_info(), bind_info.as_slice());
+        let v1 = compose(build_bind_info(&to, 1).as_slice(), &[7u8; 65]).unwrap();
+        let v1 = SignedBindInfo::parse(v1.as_slice()).unwrap();
+        assert_eq!(v1.version(), 1);
+        assert_eq!(v1.to(), to);
+        assert!(SignedBindInfo::parse(&[0u8; 3]).is_err());
+    }
+}
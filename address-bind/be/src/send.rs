@@ -0,0 +1,226 @@
+use crate::message::{SignedBindInfo, witness_args};
+use ckb_jsonrpc_types::{BlockNumber, OutputsValidator, Uint64};
+use ckb_sdk::{
+    ScriptGroup,
+    rpc::{
+        CkbRpcClient,
+        ckb_indexer::{Order, ScriptType, SearchKey, SearchKeyFilter},
+    },
+    traits::{CellDepResolver, DefaultCellDepResolver, SecpCkbRawKeySigner},
+    unlock::{ScriptSigner, SecpSighashScriptSigner},
+};
+use ckb_types::{
+    H256,
+    bytes::Bytes,
+    core::{Capacity, ScriptHashType, TransactionView},
+    packed,
+    prelude::*,
+};
+use color_eyre::{Result, eyre::eyre};
+use secp256k1::{PublicKey, Secp256k1, SecretKey};
+
+// default secp256k1 blake160 sighash all lock of key
+pub fn sighash_lock(key: &SecretKey) -> packed::Script {
+    let pubkey = PublicKey::from_secret_key(&Secp256k1::new(), key);
+    let args = &ckb_hash::blake2b_256(pubkey.serialize())[0..20];
+    packed::Script::new_builder()
+        .code_hash(ckb_sdk::constants::SIGHASH_TYPE_HASH.pack())
+        .hash_type(ScriptHashType::Type.into())
+        .args(Bytes::copy_from_slice(args).pack())
+        .build()
+}
+
+// self-transfer of one cell, the shape verify_tx expects: one input, one output with the
+// same lock, BindInfoWithSig in input_type and a placeholder for the lock signature
+pub fn build_bind_tx(
+    out_point: packed::OutPoint,
+    cell: packed::CellOutput,
+    cell_dep: packed::CellDep,
+    bind_info_with_sig: &SignedBindInfo,
+    fee_rate: u64,
+) -> Result<TransactionView> {
+    let witness = witness_args(bind_info_with_sig)
+        .as_builder()
+        .lock(Some(Bytes::from(vec![0u8; 65])).pack())
+        .build();
+    let build = |capacity: u64| {
+        TransactionView::new_advanced_builder()
+            .cell_dep(cell_dep.clone())
+            .input(packed::CellInput::new(out_point.clone(), 0))
+            .output(cell.clone().as_builder().capacity(capacity.pack()).build())
+            .output_data(Bytes::new().pack())
+            .witness(witness.as_bytes().pack())
+            .build()
+    };
+
+    // fee_rate is shannons per 1000 bytes
+    let input_capacity: u64 = cell.capacity().unpack();
+    let size = build(input_capacity).data().serialized_size_in_block() as u64;
+    let fee = (size * fee_rate).div_ceil(1000);
+    let occupied = cell
+        .occupied_capacity(Capacity::zero())
+        .map_err(|e| eyre!("calculate occupied capacity failed: {e}"))?
+        .as_u64();
+    if input_capacity < occupied + fee {
+        return Err(eyre!(
+            "cell capacity {input_capacity} is not enough for occupied {occupied} and fee {fee}"
+        ));
+    }
+    Ok(build(input_capacity - fee))
+}
+
+// fill the lock signature of input 0 with ckb-sdk
+pub fn sign_bind_tx(tx: &TransactionView, key: &SecretKey) -> Result<TransactionView> {
+    let signer =
+        SecpSighashScriptSigner::new(Box::new(SecpCkbRawKeySigner::new_with_secret_keys(vec![
+            *key,
+        ])));
+    let mut script_group = ScriptGroup::from_lock_script(&sighash_lock(key));
+    script_group.input_indices.push(0);
+    signer
+        .sign_tx(tx, &script_group)
+        .map_err(|e| eyre!("sign tx failed: {e}"))
+}
+
+// a live cell of lock without type and data, big enough to pay the fee
+fn find_input_cell(
+    ckb_client: &CkbRpcClient,
+    lock: &packed::Script,
+    min_capacity: u64,
+) -> Result<(packed::OutPoint, packed::CellOutput)> {
+    let search_key = SearchKey {
+        script: lock.clone().into(),
+        script_type: ScriptType::Lock,
+        script_search_mode: None,
+        filter: Some(SearchKeyFilter {
+            script_len_range: Some([Uint64::from(0), Uint64::from(1)]),
+            output_data_len_range: Some([Uint64::from(0), Uint64::from(1)]),
+            output_capacity_range: Some([Uint64::from(min_capacity), Uint64::from(u64::MAX)]),
+            ..Default::default()
+        }),
+        with_data: Some(false),
+        group_by_transaction: None,
+    };
+    let cells = ckb_client
+        .get_cells(search_key, Order::Asc, 1.into(), None)
+        .map_err(|e| eyre!("get_cells failed: {e}"))?;
+    let cell = cells.objects.into_iter().next().ok_or_else(|| {
+        eyre!("no live cell without type and data of at least {min_capacity} shannons")
+    })?;
+    Ok((cell.out_point.into(), cell.output.into()))
+}
+
+// build, sign and send the bind tx for bind_info_with_sig, paid by a cell of to_key
+pub async fn send_bind(
+    ckb_client: &CkbRpcClient,
+    to_key: &SecretKey,
+    bind_info_with_sig: &SignedBindInfo,
+    fee_rate: u64,
+) -> Result<H256> {
+    // the tx must transfer to the lock named in bind info
+    let to_lock = sighash_lock(to_key);
+    if bind_info_with_sig.to().as_slice() != to_lock.as_slice() {
+        return Err(eyre!("bind info is not to the lock of the given key"));
+    }
+
+    let genesis = ckb_client
+        .get_block_by_number(BlockNumber::from(0))
+        .map_err(|e| eyre!("get genesis block failed: {e}"))?
+        .ok_or_else(|| eyre!("genesis block not found"))?;
+    let genesis = ckb_types::core::BlockView::from(genesis);
+    let cell_dep_resolver = DefaultCellDepResolver::from_genesis_async(&genesis)
+        .await
+        .map_err(|e| eyre!("resolve genesis cell deps failed: {e}"))?;
+    let cell_dep = cell_dep_resolver
+        .resolve(&to_lock)
+        .ok_or_else(|| eyre!("cell dep of sighash lock not found"))?;
+
+    // 61 CKB occupied by a sighash cell, plus 1 CKB for the fee
+    let (out_point, cell) = find_input_cell(ckb_client, &to_lock, 62_0000_0000)?;
+    let tx = build_bind_tx(out_point, cell, cell_dep, bind_info_with_sig, fee_rate)?;
+    let tx = sign_bind_tx(&tx, to_key)?;
+    // the output is the sighash lock of the to key, a well known script
+    ckb_client
+        .send_transaction(
+            tx.data().into(),
+            Some(OutputsValidator::WellKnownScriptsOnly),
+        )
+        .map_err(|e| eyre!("send transaction failed: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain::MemoryChain;
+    use crate::message::{bind_info_message, build_bind_info, compose};
+    use crate::signature::neuron_sign_message;
+    use crate::verify::{VerifyOptions, verify_tx};
+    use ckb_sdk::NetworkType;
+
+    #[tokio::test]
+    async fn test_build_and_sign_bind_tx() {
+        let from_key = SecretKey::from_slice(&[0x11; 32]).unwrap();
+        let to_key = SecretKey::from_slice(&[0x22; 32]).unwrap();
+        let to_lock = sighash_lock(&to_key);
+
+        let bind_info = build_bind_info(&to_lock, 1760432079687);
+        let message = bind_info_message(bind_info.as_slice());
+        let sig = neuron_sign_message(&message, &from_key);
+        let bind_info_with_sig = compose(bind_info.as_slice(), &sig).unwrap();
+
+        // input cell owned by the to key
+        let cell = packed::CellOutput::new_builder()
+            .capacity(100_0000_0000u64.pack())
+            .lock(to_lock.clone())
+            .build();
+        let mut chain = MemoryChain::new();
+        let pre_tx = TransactionView::new_advanced_builder()
+            .output(cell.clone())
+            .output_data(Bytes::new().pack())
+            .build();
+        let pre_tx_hash = chain.insert_transaction(pre_tx.data().into());
+        let out_point = packed::OutPoint::new(pre_tx_hash.pack(), 0);
+
+        let tx = build_bind_tx(
+            out_point.clone(),
+            cell.clone(),
+            packed::CellDep::default(),
+            &bind_info_with_sig,
+            1000,
+        )
+        .unwrap();
+        let output_capacity: u64 = tx.outputs().get(0).unwrap().capacity().unpack();
+        assert!(output_capacity < 100_0000_0000 && output_capacity > 99_9999_0000);
+
+        let tx = sign_bind_tx(&tx, &to_key).unwrap();
+        let witness =
+            packed::WitnessArgs::from_slice(&tx.witnesses().get(0).unwrap().raw_data()).unwrap();
+        let lock = witness.lock().to_opt().unwrap().raw_data();
+        assert_eq!(lock.len(), 65);
+        assert_ne!(lock.as_ref(), &[0u8; 65]);
+
+        let record = verify_tx(
+            &chain,
+            NetworkType::Testnet,
+            &tx.data().into(),
+            &VerifyOptions::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(record.from_lock, sighash_lock(&from_key));
+        assert_eq!(record.to_lock, to_lock);
+
+        // nothing left to pay the fee with
+        let small = cell.as_builder().capacity(61_0000_0000u64.pack()).build();
+        assert!(
+            build_bind_tx(
+                out_point,
+                small,
+                packed::CellDep::default(),
+                &bind_info_with_sig,
+                1000
+            )
+            .is_err()
+        );
+    }
+}
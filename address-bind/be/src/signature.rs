@@ -0,0 +1,856 @@
+// native verification of CCC "Signature Object" signatures
+//
+// mirrors `ccc.signerFromSignature` + `verifyMessage` + `getRecommendedAddress`
+// in @ckb-ccc/core, so the recovered address is the same one the recoverysig
+// service would return.
+use base64::Engine;
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use ckb_sdk::{Address, NetworkType, constants::MultisigScript};
+use ckb_types::bytes::Bytes;
+use ckb_types::core::ScriptHashType;
+use ckb_types::prelude::{Builder, Entity, Pack};
+use ckb_types::{H256, packed};
+use ripemd::Ripemd160;
+use secp256k1::{Message, PublicKey, Secp256k1, SecretKey, XOnlyPublicKey, ecdsa, schnorr};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use sha3::Keccak256;
+use std::str::FromStr;
+
+// omnilock auth flags
+const OMNILOCK_FLAG_BTC: u8 = 0x04;
+const OMNILOCK_FLAG_DOGE: u8 = 0x05;
+const OMNILOCK_FLAG_EVM: u8 = 0x12;
+
+// nostr event kind used by ccc to sign plain messages
+const NOSTR_MESSAGE_KIND: u64 = 23335;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SignatureObject {
+    pub signature: String,
+    pub identity: String,
+    pub sign_type: String,
+}
+
+// parse sig bytes in BindInfoWithSig as utf8 json of ccc Signature
+pub fn parse_signature_object(sig_bytes: &[u8]) -> Result<SignatureObject, String> {
+    let sig_json =
+        std::str::from_utf8(sig_bytes).map_err(|e| format!("sig is not utf8 json: {e}"))?;
+    serde_json::from_str(sig_json).map_err(|e| format!("parse signature object failed: {e}"))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RecoverError {
+    // sign type or key type can not be verified natively
+    Unsupported(String),
+    // signature is malformed or does not match message
+    Invalid(String),
+}
+
+impl std::fmt::Display for RecoverError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RecoverError::Unsupported(msg) => write!(f, "unsupported: {msg}"),
+            RecoverError::Invalid(msg) => write!(f, "invalid: {msg}"),
+        }
+    }
+}
+
+// verify signature object over message and return lock script of signer's recommended address
+pub fn recover_lock_script(
+    message: &str,
+    sig: &SignatureObject,
+    network: NetworkType,
+) -> Result<packed::Script, RecoverError> {
+    match sig.sign_type.as_str() {
+        "CkbSecp256k1" => verify_ckb_secp256k1(message, sig),
+        "EvmPersonal" => verify_evm_personal(message, sig, network),
+        "BtcEcdsa" => verify_btc_ecdsa(message, sig, network),
+        "JoyId" => {
+            check_joyid_supported(sig)?;
+            verify_joyid(message, sig, network)
+        }
+        "NostrEvent" => verify_nostr_event(message, sig, network),
+        "DogeEcdsa" => verify_doge_ecdsa(message, sig, network),
+        sign_type => {
+            return Err(RecoverError::Unsupported(format!("sign type {sign_type}")));
+        }
+    }
+    .map_err(RecoverError::Invalid)
+}
+
+fn decode_hex(s: &str) -> Result<Vec<u8>, String> {
+    hex::decode(s.strip_prefix("0x").unwrap_or(s)).map_err(|e| format!("decode hex failed: {e}"))
+}
+
+fn blake160(data: &[u8]) -> Vec<u8> {
+    ckb_hash::blake2b_256(data)[0..20].to_vec()
+}
+
+fn hash160(data: &[u8]) -> Vec<u8> {
+    Ripemd160::digest(Sha256::digest(data)).to_vec()
+}
+
+fn build_script(code_hash: &str, args: Vec<u8>) -> packed::Script {
+    packed::Script::new_builder()
+        .code_hash(H256::from_str(code_hash).unwrap().pack())
+        .hash_type(ScriptHashType::Type.into())
+        .args(Bytes::from(args).pack())
+        .build()
+}
+
+fn omnilock_script(flag: u8, auth: &[u8], network: NetworkType) -> packed::Script {
+    let code_hash = match network {
+        NetworkType::Mainnet => "9b819793a64463aed77c615d6cb226eea5487ccfc0783043a587254cda2b6f26",
+        _ => "f329effd1c475a2978453c8600e1eaf0bc2087ee093c3ee64cc96ec6847752cb",
+    };
+    let mut args = vec![flag];
+    args.extend_from_slice(auth);
+    // omnilock flags, no extra features
+    args.push(0x00);
+    build_script(code_hash, args)
+}
+
+fn joyid_script(args: Vec<u8>, network: NetworkType) -> packed::Script {
+    let code_hash = match network {
+        NetworkType::Mainnet => "d00c84f0ec8fd441c38bc3f87a371f547190f2fcff88e642bc5bf54b9e318323",
+        _ => "d23761b364210735c19c60561d213fb3beae2fd6172743719eff6920e020baac",
+    };
+    build_script(code_hash, args)
+}
+
+fn nostr_lock_script(args: Vec<u8>, network: NetworkType) -> packed::Script {
+    let code_hash = match network {
+        NetworkType::Mainnet => "641a89ad2f77721b803cd50d01351c1f308444072d5fa20088567196c0574c68",
+        _ => "6ae5ee0cb887b2df5a9a18137315b9bdc55be8d52637b2de0624092d5f0c91d5",
+    };
+    build_script(code_hash, args)
+}
+
+fn secp256k1_verify(digest: [u8; 32], sig: &[u8], pubkey: &PublicKey) -> Result<(), String> {
+    if sig.len() < 64 {
+        return Err(format!("invalid signature length: {}", sig.len()));
+    }
+    let sig = ecdsa::Signature::from_compact(&sig[0..64])
+        .map_err(|e| format!("parse signature failed: {e}"))?;
+    Secp256k1::verification_only()
+        .verify_ecdsa(&Message::from_digest(digest), &sig, pubkey)
+        .map_err(|e| format!("verify signature failed: {e}"))
+}
+
+pub fn secp256k1_recover(
+    digest: [u8; 32],
+    sig: &[u8],
+    recovery_id: u8,
+) -> Result<PublicKey, String> {
+    let id = ecdsa::RecoveryId::try_from(i32::from(recovery_id))
+        .map_err(|e| format!("invalid recovery id: {e}"))?;
+    let sig = ecdsa::RecoverableSignature::from_compact(sig, id)
+        .map_err(|e| format!("parse signature failed: {e}"))?;
+    Secp256k1::new()
+        .recover_ecdsa(&Message::from_digest(digest), &sig)
+        .map_err(|e| format!("recover error: {e}"))
+}
+
+// same digest as neuron signMessage
+pub fn ckb_message_hash(message: &str) -> [u8; 32] {
+    ckb_hash::blake2b_256(format!("Nervos Message:{message}").as_bytes())
+}
+
+// 65 bytes r || s || recovery id over ckb_message_hash, as neuron signMessage produces
+pub fn neuron_sign_message(message: &str, key: &SecretKey) -> [u8; 65] {
+    let sig = Secp256k1::new()
+        .sign_ecdsa_recoverable(&Message::from_digest(ckb_message_hash(message)), key);
+    let (recovery_id, compact) = sig.serialize_compact();
+    let mut sig_bytes = [0u8; 65];
+    sig_bytes[..64].copy_from_slice(&compact);
+    sig_bytes[64] = i32::from(recovery_id) as u8;
+    sig_bytes
+}
+
+// multisig sig in BindInfoWithSig: the multisig script `S | R | M | N | blake160 * N` of the
+// sighash_all multisig lock, the optional 8 bytes since of a time-locked multisig address,
+// then M neuron signatures over the same message.
+// S is reserved in the lock and always 0 there, here it names the lock, see multisig_s
+pub struct MultisigSig<'a> {
+    pub multisig: MultisigScript,
+    pub multisig_script: &'a [u8],
+    pub since: Option<[u8; 8]>,
+    pub signatures: Vec<&'a [u8]>,
+}
+
+pub const fn multisig_s(multisig: MultisigScript) -> u8 {
+    match multisig {
+        MultisigScript::Legacy => 0,
+        MultisigScript::V2 => 1,
+    }
+}
+
+// json of Signature Object never starts with S
+pub const fn is_multisig_sig(sig_bytes: &[u8]) -> bool {
+    matches!(sig_bytes.first(), Some(0 | 1))
+}
+
+pub fn parse_multisig_sig(sig_bytes: &[u8]) -> Result<MultisigSig<'_>, String> {
+    if sig_bytes.len() < 4 {
+        return Err("invalid multisig script".to_string());
+    }
+    let multisig = match sig_bytes[0] {
+        0 => MultisigScript::Legacy,
+        1 => MultisigScript::V2,
+        s => return Err(format!("unknown multisig lock {s}")),
+    };
+    let (require_first_n, threshold, pubkeys_cnt) = (
+        sig_bytes[1] as usize,
+        sig_bytes[2] as usize,
+        sig_bytes[3] as usize,
+    );
+    if threshold == 0 || pubkeys_cnt < threshold || threshold < require_first_n {
+        return Err(format!(
+            "invalid multisig config {require_first_n}/{threshold}/{pubkeys_cnt}"
+        ));
+    }
+    let script_len = 4 + 20 * pubkeys_cnt;
+    let sigs_len = 65 * threshold;
+    let since = match sig_bytes.len().checked_sub(script_len + sigs_len) {
+        Some(0) => None,
+        Some(8) => Some(sig_bytes[script_len..script_len + 8].try_into().unwrap()),
+        _ => return Err(format!("invalid multisig sig length: {}", sig_bytes.len())),
+    };
+    let sigs_start = sig_bytes.len() - sigs_len;
+    Ok(MultisigSig {
+        multisig,
+        multisig_script: &sig_bytes[..script_len],
+        since,
+        signatures: sig_bytes[sigs_start..].chunks(65).collect(),
+    })
+}
+
+// check the signatures like the multisig lock does and return the multisig lock
+pub fn recover_multisig_lock(
+    message: &str,
+    sig: &MultisigSig<'_>,
+) -> Result<packed::Script, String> {
+    let require_first_n = sig.multisig_script[1] as usize;
+    let pubkey_hashes: Vec<&[u8]> = sig.multisig_script[4..].chunks(20).collect();
+    let digest = ckb_message_hash(message);
+    let mut used = vec![false; pubkey_hashes.len()];
+    for signature in &sig.signatures {
+        let pubkey = secp256k1_recover(digest, &signature[0..64], signature[64])?;
+        let pubkey_hash = blake160(&pubkey.serialize());
+        let index = (0..pubkey_hashes.len())
+            .find(|&i| !used[i] && pubkey_hashes[i] == pubkey_hash)
+            .ok_or_else(|| "signer is not a member of multisig".to_string())?;
+        used[index] = true;
+    }
+    if used[..require_first_n].iter().any(|used| !used) {
+        return Err(format!("first {require_first_n} members must sign"));
+    }
+
+    // args hash the multisig script as in the lock witness, with S = 0
+    let mut multisig_script = sig.multisig_script.to_vec();
+    multisig_script[0] = 0;
+    let mut args = blake160(&multisig_script);
+    if let Some(since) = sig.since {
+        args.extend_from_slice(&since);
+    }
+    let script_id = sig.multisig.script_id();
+    Ok(packed::Script::new_builder()
+        .code_hash(script_id.code_hash.pack())
+        .hash_type(script_id.hash_type.into())
+        .args(Bytes::from(args).pack())
+        .build())
+}
+
+fn verify_ckb_secp256k1(message: &str, sig: &SignatureObject) -> Result<packed::Script, String> {
+    let pubkey_bytes = decode_hex(&sig.identity)?;
+    let pubkey =
+        PublicKey::from_slice(&pubkey_bytes).map_err(|e| format!("invalid public key: {e}"))?;
+    secp256k1_verify(
+        ckb_message_hash(message),
+        &decode_hex(&sig.signature)?,
+        &pubkey,
+    )?;
+
+    Ok(build_script(
+        "9bd7e06f3ecf4be0f2fcd2188b23f1b9fcc88e5d4b65a8637b17723bbda3cce8",
+        blake160(&pubkey.serialize()),
+    ))
+}
+
+fn verify_evm_personal(
+    message: &str,
+    sig: &SignatureObject,
+    network: NetworkType,
+) -> Result<packed::Script, String> {
+    let sig_bytes = decode_hex(&sig.signature)?;
+    if sig_bytes.len() != 65 {
+        return Err(format!("invalid evm signature length: {}", sig_bytes.len()));
+    }
+    let recovery_id = match sig_bytes[64] {
+        v @ 0..=1 => v,
+        v @ 27..=28 => v - 27,
+        v => return Err(format!("invalid evm signature v: {v}")),
+    };
+
+    // EIP-191 personal_sign
+    let mut hasher = Keccak256::new();
+    hasher.update(format!("\x19Ethereum Signed Message:\n{}", message.len()).as_bytes());
+    hasher.update(message.as_bytes());
+    let digest: [u8; 32] = hasher.finalize().into();
+
+    let pubkey = secp256k1_recover(digest, &sig_bytes[0..64], recovery_id)?;
+    let evm_address = Keccak256::digest(&pubkey.serialize_uncompressed()[1..])[12..].to_vec();
+    if decode_hex(&sig.identity)? != evm_address {
+        return Err("evm address not match signature".to_string());
+    }
+
+    Ok(omnilock_script(OMNILOCK_FLAG_EVM, &evm_address, network))
+}
+
+// bitcoinjs-message style magic hash: sha256d(varint(prefix) prefix varint(msg) msg)
+fn magic_hash(prefix: &str, message: &str) -> [u8; 32] {
+    fn push_varint(buf: &mut Vec<u8>, n: usize) {
+        match n {
+            0..0xfd => buf.push(n as u8),
+            0xfd..=0xffff => {
+                buf.push(0xfd);
+                buf.extend_from_slice(&(n as u16).to_le_bytes());
+            }
+            _ => {
+                buf.push(0xfe);
+                buf.extend_from_slice(&(n as u32).to_le_bytes());
+            }
+        }
+    }
+    let mut buf = vec![];
+    push_varint(&mut buf, prefix.len());
+    buf.extend_from_slice(prefix.as_bytes());
+    push_varint(&mut buf, message.len());
+    buf.extend_from_slice(message.as_bytes());
+    Sha256::digest(Sha256::digest(&buf)).into()
+}
+
+fn verify_btc_ecdsa(
+    message: &str,
+    sig: &SignatureObject,
+    network: NetworkType,
+) -> Result<packed::Script, String> {
+    let pubkey_bytes = decode_hex(&sig.identity)?;
+    let pubkey =
+        PublicKey::from_slice(&pubkey_bytes).map_err(|e| format!("invalid public key: {e}"))?;
+    let sig_bytes = STANDARD
+        .decode(&sig.signature)
+        .map_err(|e| format!("decode base64 signature failed: {e}"))?;
+    if sig_bytes.len() != 65 {
+        return Err(format!("invalid btc signature length: {}", sig_bytes.len()));
+    }
+    let digest = magic_hash("Bitcoin Signed Message:\n", message);
+    secp256k1_verify(digest, &sig_bytes[1..], &pubkey)?;
+
+    Ok(omnilock_script(
+        OMNILOCK_FLAG_BTC,
+        &hash160(&pubkey.serialize()),
+        network,
+    ))
+}
+
+fn verify_doge_ecdsa(
+    message: &str,
+    sig: &SignatureObject,
+    network: NetworkType,
+) -> Result<packed::Script, String> {
+    // identity is a base58check P2PKH dogecoin address
+    let address = bs58::decode(&sig.identity)
+        .with_check(None)
+        .into_vec()
+        .map_err(|e| format!("decode doge address failed: {e}"))?;
+    if address.len() != 21 {
+        return Err(format!("invalid doge address length: {}", address.len()));
+    }
+    let address_hash = &address[1..];
+
+    let sig_bytes = STANDARD
+        .decode(&sig.signature)
+        .map_err(|e| format!("decode base64 signature failed: {e}"))?;
+    if sig_bytes.len() != 65 || !(27..=34).contains(&sig_bytes[0]) {
+        return Err("invalid doge signature".to_string());
+    }
+    let compressed = sig_bytes[0] >= 31;
+    let recovery_id = (sig_bytes[0] - 27) & 3;
+    let digest = magic_hash("Dogecoin Signed Message:\n", message);
+    let pubkey = secp256k1_recover(digest, &sig_bytes[1..], recovery_id)?;
+    let pubkey_hash = if compressed {
+        hash160(&pubkey.serialize())
+    } else {
+        hash160(&pubkey.serialize_uncompressed())
+    };
+    if pubkey_hash != address_hash {
+        return Err("doge address not match signature".to_string());
+    }
+
+    Ok(omnilock_script(OMNILOCK_FLAG_DOGE, address_hash, network))
+}
+
+fn verify_nostr_event(
+    message: &str,
+    sig: &SignatureObject,
+    network: NetworkType,
+) -> Result<packed::Script, String> {
+    let pubkey_bytes = decode_hex(&sig.identity)?;
+    let pubkey = XOnlyPublicKey::from_slice(&pubkey_bytes)
+        .map_err(|e| format!("invalid nostr public key: {e}"))?;
+    let sig_bytes = decode_hex(&sig.signature)?;
+    let signature = schnorr::Signature::from_slice(&sig_bytes)
+        .map_err(|e| format!("parse nostr signature failed: {e}"))?;
+
+    // NIP-01 event id of the unsigned event ccc builds for a plain message
+    let event = serde_json::json!([
+        0,
+        hex::encode(&pubkey_bytes),
+        0,
+        NOSTR_MESSAGE_KIND,
+        [],
+        message
+    ]);
+    let event_id = Sha256::digest(event.to_string().as_bytes());
+    Secp256k1::verification_only()
+        .verify_schnorr(&signature, &event_id, &pubkey)
+        .map_err(|e| format!("verify nostr signature failed: {e}"))?;
+
+    let mut args = vec![0x00];
+    args.extend_from_slice(&blake160(&pubkey_bytes));
+    Ok(nostr_lock_script(args, network))
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct JoyIdIdentity {
+    public_key: String,
+    key_type: String,
+    address: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct JoyIdSignature {
+    alg: i64,
+    signature: String,
+    message: String,
+}
+
+// only main key with secp256r1 can be verified offline, sub keys need cota cells
+fn check_joyid_supported(sig: &SignatureObject) -> Result<(), RecoverError> {
+    let identity: JoyIdIdentity = serde_json::from_str(&sig.identity)
+        .map_err(|e| RecoverError::Invalid(format!("parse joyid identity failed: {e}")))?;
+    let joyid_sig: JoyIdSignature = serde_json::from_str(&sig.signature)
+        .map_err(|e| RecoverError::Invalid(format!("parse joyid signature failed: {e}")))?;
+    if identity.key_type != "main_key" {
+        return Err(RecoverError::Unsupported(format!(
+            "joyid key type {}",
+            identity.key_type
+        )));
+    }
+    if joyid_sig.alg != -7 {
+        return Err(RecoverError::Unsupported(format!(
+            "joyid alg {}",
+            joyid_sig.alg
+        )));
+    }
+    Ok(())
+}
+
+// joyid webauthn signature of main key
+fn verify_joyid(
+    message: &str,
+    sig: &SignatureObject,
+    network: NetworkType,
+) -> Result<packed::Script, String> {
+    use p256::ecdsa::signature::Verifier;
+
+    let identity: JoyIdIdentity = serde_json::from_str(&sig.identity)
+        .map_err(|e| format!("parse joyid identity failed: {e}"))?;
+    let joyid_sig: JoyIdSignature = serde_json::from_str(&sig.signature)
+        .map_err(|e| format!("parse joyid signature failed: {e}"))?;
+
+    // message is authenticator data (37 bytes) followed by client data json
+    let webauthn_message = decode_hex(&joyid_sig.message)?;
+    if webauthn_message.len() <= 37 {
+        return Err("invalid joyid webauthn message".to_string());
+    }
+    let (auth_data, client_data) = webauthn_message.split_at(37);
+    let client_data_json: serde_json::Value = serde_json::from_slice(client_data)
+        .map_err(|e| format!("parse joyid client data failed: {e}"))?;
+    if client_data_json["challenge"].as_str() != Some(&URL_SAFE_NO_PAD.encode(message)) {
+        return Err("joyid challenge not match message".to_string());
+    }
+
+    let mut pubkey_bytes = decode_hex(&identity.public_key)?;
+    if pubkey_bytes.len() == 64 {
+        pubkey_bytes.insert(0, 0x04);
+    }
+    let verifying_key = p256::ecdsa::VerifyingKey::from_sec1_bytes(&pubkey_bytes)
+        .map_err(|e| format!("invalid joyid public key: {e}"))?;
+    let sig_bytes = decode_hex(&joyid_sig.signature)?;
+    let signature = p256::ecdsa::Signature::from_slice(&sig_bytes)
+        .or_else(|_| p256::ecdsa::Signature::from_der(&sig_bytes))
+        .map_err(|e| format!("parse joyid signature failed: {e}"))?;
+    let mut signed_data = auth_data.to_vec();
+    signed_data.extend_from_slice(&Sha256::digest(client_data));
+    verifying_key
+        .verify(&signed_data, &signature)
+        .map_err(|e| format!("verify joyid signature failed: {e}"))?;
+
+    // main key lock args: 0x0001 + blake160(x || y)
+    let mut args = vec![0x00, 0x01];
+    args.extend_from_slice(&blake160(&pubkey_bytes[1..]));
+    let lock_script = joyid_script(args, network);
+    let identity_address =
+        Address::from_str(&identity.address).map_err(|e| format!("invalid joyid address: {e}"))?;
+    if packed::Script::from(identity_address.payload()).as_slice() != lock_script.as_slice() {
+        return Err("joyid address not match public key".to_string());
+    }
+
+    Ok(lock_script)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ckb_sdk::AddressPayload;
+    use secp256k1::Keypair;
+
+    const MESSAGE: &str = "0x5500000010000000510000004900000010000000300000003100000048";
+
+    fn recover_address(
+        message: &str,
+        sig: &SignatureObject,
+        network: NetworkType,
+    ) -> Result<String, RecoverError> {
+        let lock_script = recover_lock_script(message, sig, network)?;
+        Ok(Address::new(network, AddressPayload::from(lock_script), true).to_string())
+    }
+
+    fn secret_key() -> SecretKey {
+        SecretKey::from_slice(&[0x11; 32]).unwrap()
+    }
+
+    #[test]
+    fn test_ckb_secp256k1() {
+        let secp = Secp256k1::new();
+        let sk = secret_key();
+        let pk = PublicKey::from_secret_key(&secp, &sk);
+        let sig_bytes = neuron_sign_message(MESSAGE, &sk);
+
+        let sig = SignatureObject {
+            signature: format!("0x{}", hex::encode(sig_bytes)),
+            identity: format!("0x{}", hex::encode(pk.serialize())),
+            sign_type: "CkbSecp256k1".to_string(),
+        };
+        let address = recover_address(MESSAGE, &sig, NetworkType::Testnet).unwrap();
+        let expected = Address::new(
+            NetworkType::Testnet,
+            AddressPayload::from(build_script(
+                "9bd7e06f3ecf4be0f2fcd2188b23f1b9fcc88e5d4b65a8637b17723bbda3cce8",
+                blake160(&pk.serialize()),
+            )),
+            true,
+        );
+        assert_eq!(address, expected.to_string());
+
+        // signature over another message must fail
+        assert!(recover_address("0x00", &sig, NetworkType::Testnet).is_err());
+    }
+
+    #[test]
+    fn test_neuron_sign_message() {
+        // BindInfo to ckt1qzda0cr08m85hc8jlnfp3zer7xulejywt49kt2rr0vthywaa50xwsqwu8lmjcalepgp5k6d4j0mtxwww68v9m6qz0q8ah at 1760432079687
+        let message = "0x5d0000000c00000055000000490000001000000030000000310000009bd7e06f3ecf4be0f2fcd2188b23f1b9fcc88e5d4b65a8637b17723bbda3cce80114000000dc3ff72c77f90a034b69b593f6b339ced1d85de847c3ede199010000";
+        let sig = neuron_sign_message(message, &secret_key());
+        // rfc6979 signatures are deterministic
+        assert_eq!(
+            hex::encode(sig),
+            "84f473d491569bdeb65adcf776ab94759f7701dc38c0a5b6500a6302a0667ec03478812336f696e41fca500983313ed37989855bde131d045defe5de31929f0a01"
+        );
+        let pk = secp256k1_recover(ckb_message_hash(message), &sig[..64], sig[64]).unwrap();
+        assert_eq!(
+            pk,
+            PublicKey::from_secret_key(&Secp256k1::new(), &secret_key())
+        );
+    }
+
+    #[test]
+    fn test_multisig() {
+        let secp = Secp256k1::new();
+        let keys: Vec<SecretKey> = (1..=3u8)
+            .map(|i| SecretKey::from_slice(&[i; 32]).unwrap())
+            .collect();
+        // 2 of 3, the first member must sign
+        let mut multisig_script = vec![0, 1, 2, 3];
+        for key in &keys {
+            multisig_script.extend(blake160(
+                &PublicKey::from_secret_key(&secp, key).serialize(),
+            ));
+        }
+        let sign = |members: &[usize], since: Option<u64>| {
+            let mut sig = multisig_script.clone();
+            if let Some(since) = since {
+                sig.extend_from_slice(&since.to_le_bytes());
+            }
+            for &i in members {
+                sig.extend_from_slice(&neuron_sign_message(MESSAGE, &keys[i]));
+            }
+            sig
+        };
+        let recover = |sig: &[u8]| recover_multisig_lock(MESSAGE, &parse_multisig_sig(sig)?);
+
+        let lock = recover(&sign(&[0, 2], None)).unwrap();
+        assert_eq!(
+            lock.code_hash().raw_data().as_ref(),
+            MultisigScript::Legacy.script_id().code_hash.as_bytes()
+        );
+        assert_eq!(lock.args().raw_data(), blake160(&multisig_script));
+        // signatures in any order
+        assert_eq!(recover(&sign(&[2, 0], None)).unwrap(), lock);
+
+        // the same members under multisig v2
+        let mut sig = sign(&[0, 2], None);
+        sig[0] = multisig_s(MultisigScript::V2);
+        let v2 = recover(&sig).unwrap();
+        let script_id = MultisigScript::V2.script_id();
+        assert_eq!(
+            v2.code_hash().raw_data().as_ref(),
+            script_id.code_hash.as_bytes()
+        );
+        assert_eq!(v2.hash_type(), script_id.hash_type.into());
+        assert_eq!(v2.args(), lock.args());
+        sig[0] = 2;
+        assert!(recover(&sig).is_err());
+
+        // time-locked multisig address carries the since in args
+        let since = 0x2000_0000_0000_0100;
+        let locked = recover(&sign(&[0, 1], Some(since))).unwrap();
+        assert_eq!(locked.args().raw_data()[..20], blake160(&multisig_script));
+        assert_eq!(locked.args().raw_data()[20..], since.to_le_bytes());
+
+        // first member missing, same member twice, not enough signatures
+        assert!(recover(&sign(&[1, 2], None)).is_err());
+        assert!(recover(&sign(&[0, 0], None)).is_err());
+        assert!(recover(&sign(&[0], None)).is_err());
+        // signer not a member
+        let mut sig = sign(&[0], None);
+        sig.extend_from_slice(&neuron_sign_message(
+            MESSAGE,
+            &SecretKey::from_slice(&[9; 32]).unwrap(),
+        ));
+        assert!(recover(&sig).is_err());
+        assert!(
+            recover_multisig_lock("0x00", &parse_multisig_sig(&sign(&[0, 1], None)).unwrap())
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_evm_personal() {
+        let secp = Secp256k1::new();
+        let sk = secret_key();
+        let pk = PublicKey::from_secret_key(&secp, &sk);
+        let evm_address = Keccak256::digest(&pk.serialize_uncompressed()[1..])[12..].to_vec();
+
+        let mut hasher = Keccak256::new();
+        hasher.update(format!("\x19Ethereum Signed Message:\n{}", MESSAGE.len()).as_bytes());
+        hasher.update(MESSAGE.as_bytes());
+        let digest: [u8; 32] = hasher.finalize().into();
+        let sig = secp.sign_ecdsa_recoverable(&Message::from_digest(digest), &sk);
+        let (id, compact) = sig.serialize_compact();
+        let mut sig_bytes = compact.to_vec();
+        sig_bytes.push(27 + i32::from(id) as u8);
+
+        let sig = SignatureObject {
+            signature: format!("0x{}", hex::encode(sig_bytes)),
+            identity: format!("0x{}", hex::encode(&evm_address)),
+            sign_type: "EvmPersonal".to_string(),
+        };
+        let address = recover_address(MESSAGE, &sig, NetworkType::Testnet).unwrap();
+        let expected = Address::new(
+            NetworkType::Testnet,
+            AddressPayload::from(omnilock_script(
+                OMNILOCK_FLAG_EVM,
+                &evm_address,
+                NetworkType::Testnet,
+            )),
+            true,
+        );
+        assert_eq!(address, expected.to_string());
+    }
+
+    #[test]
+    fn test_btc_ecdsa() {
+        let secp = Secp256k1::new();
+        let sk = secret_key();
+        let pk = PublicKey::from_secret_key(&secp, &sk);
+        let digest = magic_hash("Bitcoin Signed Message:\n", MESSAGE);
+        let sig = secp.sign_ecdsa_recoverable(&Message::from_digest(digest), &sk);
+        let (id, compact) = sig.serialize_compact();
+        let mut sig_bytes = vec![31 + i32::from(id) as u8];
+        sig_bytes.extend_from_slice(&compact);
+
+        let sig = SignatureObject {
+            signature: STANDARD.encode(&sig_bytes),
+            identity: hex::encode(pk.serialize()),
+            sign_type: "BtcEcdsa".to_string(),
+        };
+        assert!(recover_address(MESSAGE, &sig, NetworkType::Testnet).is_ok());
+        assert!(recover_address("0x00", &sig, NetworkType::Testnet).is_err());
+    }
+
+    #[test]
+    fn test_doge_ecdsa() {
+        let secp = Secp256k1::new();
+        let sk = secret_key();
+        let pk = PublicKey::from_secret_key(&secp, &sk);
+        let pubkey_hash = hash160(&pk.serialize());
+        // P2PKH address on dogecoin mainnet
+        let mut address = vec![0x1e];
+        address.extend_from_slice(&pubkey_hash);
+        let digest = magic_hash("Dogecoin Signed Message:\n", MESSAGE);
+        let sig = secp.sign_ecdsa_recoverable(&Message::from_digest(digest), &sk);
+        let (id, compact) = sig.serialize_compact();
+        let mut sig_bytes = vec![31 + i32::from(id) as u8];
+        sig_bytes.extend_from_slice(&compact);
+
+        let sig = SignatureObject {
+            signature: STANDARD.encode(&sig_bytes),
+            identity: bs58::encode(address).with_check().into_string(),
+            sign_type: "DogeEcdsa".to_string(),
+        };
+        let expected = Address::new(
+            NetworkType::Testnet,
+            AddressPayload::from(omnilock_script(
+                OMNILOCK_FLAG_DOGE,
+                &pubkey_hash,
+                NetworkType::Testnet,
+            )),
+            true,
+        );
+        assert_eq!(
+            recover_address(MESSAGE, &sig, NetworkType::Testnet).unwrap(),
+            expected.to_string()
+        );
+        assert!(recover_address("0x00", &sig, NetworkType::Testnet).is_err());
+    }
+
+    #[test]
+    fn test_nostr_event() {
+        let secp = Secp256k1::new();
+        let keypair = Keypair::from_secret_key(&secp, &secret_key());
+        let pubkey = hex::encode(keypair.x_only_public_key().0.serialize());
+        // NIP-01 serialization of the event ccc signs
+        let event = format!(r#"[0,"{pubkey}",0,23335,[],"{MESSAGE}"]"#);
+        let event_id = Sha256::digest(event.as_bytes());
+        let signature = secp.sign_schnorr_no_aux_rand(&event_id, &keypair);
+
+        let sig = SignatureObject {
+            signature: hex::encode(signature.to_byte_array()),
+            identity: pubkey.clone(),
+            sign_type: "NostrEvent".to_string(),
+        };
+        let mut args = vec![0x00];
+        args.extend(blake160(&hex::decode(&pubkey).unwrap()));
+        let expected = Address::new(
+            NetworkType::Testnet,
+            AddressPayload::from(nostr_lock_script(args, NetworkType::Testnet)),
+            true,
+        );
+        assert_eq!(
+            recover_address(MESSAGE, &sig, NetworkType::Testnet).unwrap(),
+            expected.to_string()
+        );
+        assert!(recover_address("0x00", &sig, NetworkType::Testnet).is_err());
+    }
+
+    #[test]
+    fn test_joyid() {
+        use p256::ecdsa::{Signature, SigningKey, signature::Signer};
+
+        let key = SigningKey::from_slice(&[0x11; 32]).unwrap();
+        let point = key.verifying_key().to_encoded_point(false);
+        let public_key = &point.as_bytes()[1..];
+        let mut args = vec![0x00, 0x01];
+        args.extend(blake160(public_key));
+        let address = Address::new(
+            NetworkType::Testnet,
+            AddressPayload::from(joyid_script(args, NetworkType::Testnet)),
+            true,
+        )
+        .to_string();
+
+        // webauthn assertion over the message as challenge
+        let sign = |message: &str, key_type: &str| {
+            let auth_data = [0x5a; 37];
+            let client_data = format!(
+                r#"{{"type":"webauthn.get","challenge":"{}","origin":"https://app.joy.id"}}"#,
+                URL_SAFE_NO_PAD.encode(message)
+            );
+            let mut signed_data = auth_data.to_vec();
+            signed_data.extend_from_slice(&Sha256::digest(client_data.as_bytes()));
+            let signature: Signature = key.sign(&signed_data);
+            let mut webauthn_message = auth_data.to_vec();
+            webauthn_message.extend_from_slice(client_data.as_bytes());
+            SignatureObject {
+                signature: serde_json::json!({
+                    "alg": -7,
+                    "signature": hex::encode(signature.to_bytes()),
+                    "message": hex::encode(webauthn_message),
+                })
+                .to_string(),
+                identity: serde_json::json!({
+                    "publicKey": hex::encode(public_key),
+                    "keyType": key_type,
+                    "address": address,
+                })
+                .to_string(),
+                sign_type: "JoyId".to_string(),
+            }
+        };
+
+        let sig = sign(MESSAGE, "main_key");
+        assert_eq!(
+            recover_address(MESSAGE, &sig, NetworkType::Testnet).unwrap(),
+            address
+        );
+        // challenge of another message
+        assert!(matches!(
+            recover_address(MESSAGE, &sign("0x00", "main_key"), NetworkType::Testnet),
+            Err(RecoverError::Invalid(_))
+        ));
+        // sub keys are checked against cota cells, left to the recovery service
+        assert!(matches!(
+            recover_address(MESSAGE, &sign(MESSAGE, "sub_key"), NetworkType::Testnet),
+            Err(RecoverError::Unsupported(_))
+        ));
+    }
+
+    // vector from recoverysig README, generated by ccc
+    #[test]
+    fn test_ccc_vector() {
+        let message = "0x5d0000000c00000055000000490000001000000030000000310000009bd7e06f3ecf4be0f2fcd2188b23f1b9fcc88e5d4b65a8637b17723bbda3cce80114000000dc3ff72c77f90a034b69b593f6b339ced1d85de88fd374e799010000";
+        let sig = parse_signature_object(br#"{"signature":"0xb286e3099df24fcc907aa75acd968c9ed8b1121c709e117e642490015ac7844b7ec2af9bf009575128d32097f36fa7bff78bd9ef7e3dbb50796eae70d21fa02000","identity":"0x033c158382c249e79fde591ac3542afc08395fdf0e3ca73587958cbe446d431a6d","signType":"CkbSecp256k1"}"#).unwrap();
+        assert_eq!(
+            recover_address(message, &sig, NetworkType::Testnet).unwrap(),
+            "ckt1qzda0cr08m85hc8jlnfp3zer7xulejywt49kt2rr0vthywaa50xwsqwu8lmjcalepgp5k6d4j0mtxwww68v9m6qz0q8ah"
+        );
+    }
+
+    #[test]
+    fn test_unknown_sign_type() {
+        let sig = parse_signature_object(
+            br#"{"signature":"0x00","identity":"0x00","signType":"Unknown"}"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            recover_address(MESSAGE, &sig, NetworkType::Testnet),
+            Err(RecoverError::Unsupported(_))
+        ));
+    }
+}
@@ -0,0 +1,758 @@
+mod postgres;
+mod sql;
+mod sqlite;
+
+pub use sql::SqlStorage;
+
+use crate::{error::RejectReason, verify::BindRecord};
+use async_trait::async_trait;
+use ckb_types::packed;
+use color_eyre::Result;
+use sqlx::FromRow;
+use std::sync::Arc;
+
+pub const BIND_COLUMNS: &str = "from_addr, to_addr, timestamp, height, tx_index, tx_hash, block_hash, from_lock, from_lock_hash, to_lock, to_lock_hash, sig_scheme, payload, digest";
+
+// statements below are plain sql accepted by every backend
+const INSERT_BIND: &str =
+    "INSERT INTO bind_info (from_addr, to_addr, timestamp, height, tx_index, tx_hash, block_hash,
+        from_lock, from_lock_hash, to_lock, to_lock_hash, sig_scheme, payload, digest)
+     VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)
+     ON CONFLICT (from_addr, to_addr, timestamp) DO NOTHING";
+
+const INSERT_REJECTED: &str =
+    "INSERT INTO rejected_bind (tx_hash, height, tx_index, to_addr, from_addr, reason, message,
+        to_lock_hash)
+     VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+     ON CONFLICT (tx_hash) DO NOTHING";
+
+const UPSERT_BLOCK: &str =
+    "INSERT INTO block_info (height, hash, parent_hash, timestamp) VALUES ($1, $2, $3, $4)
+     ON CONFLICT (height) DO UPDATE SET hash = excluded.hash, parent_hash = excluded.parent_hash,
+        timestamp = excluded.timestamp";
+
+// bindings of from within heights $2 ..= $3, a page of $6 before ($4, $5)
+fn select_by_from() -> String {
+    format!(
+        "SELECT {BIND_COLUMNS}
+         FROM bind_info
+         WHERE from_addr = $1 AND height BETWEEN $2 AND $3 AND (height, tx_index) < ($4, $5)
+         ORDER BY height DESC, tx_index DESC
+         LIMIT $6"
+    )
+}
+
+const COUNT_BY_FROM: &str =
+    "SELECT COUNT(*) FROM bind_info WHERE from_addr = $1 AND height BETWEEN $2 AND $3";
+
+// placeholders of the n addresses of a batch query, numbered from first
+fn batch_params(first: usize, n: usize) -> String {
+    (first..first + n)
+        .map(|i| format!("${i}"))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+// the newest $2 bindings of each partition among bindings
+fn newest_per(partition: &str, bindings: &str) -> String {
+    format!(
+        "SELECT {BIND_COLUMNS} FROM (
+             SELECT *, ROW_NUMBER() OVER (
+                 PARTITION BY {partition} ORDER BY height DESC, tx_index DESC
+             ) AS row_num
+             FROM ({bindings}) AS bindings
+         ) AS numbered
+         WHERE row_num <= $2
+         ORDER BY {partition}, height DESC, tx_index DESC"
+    )
+}
+
+// bindings of every from in the batch, given by address or lock hash, at most $2 of each
+fn select_by_froms(n: usize) -> String {
+    let froms = batch_params(3, n);
+    newest_per(
+        "from_addr",
+        &format!(
+            "SELECT * FROM bind_info
+             WHERE height <= $1 AND (from_addr IN ({froms}) OR from_lock_hash IN ({froms}))"
+        ),
+    )
+}
+
+// keep the binding with the latest signed timestamp, then the latest in chain order
+const UPSERT_ACTIVE: &str =
+    "INSERT INTO active_binding (from_addr, to_addr, timestamp, height, tx_index)
+     VALUES ($1, $2, $3, $4, $5)
+     ON CONFLICT (from_addr) DO UPDATE SET to_addr = excluded.to_addr,
+        timestamp = excluded.timestamp, height = excluded.height, tx_index = excluded.tx_index
+     WHERE (excluded.timestamp, excluded.height, excluded.tx_index)
+        > (active_binding.timestamp, active_binding.height, active_binding.tx_index)";
+
+// rank the bindings of each from, rank 1 is the one in effect
+fn ranked_bindings(filter: &str) -> String {
+    format!(
+        "SELECT *, ROW_NUMBER() OVER (
+             PARTITION BY from_addr ORDER BY timestamp DESC, height DESC, tx_index DESC
+         ) AS rank
+         FROM bind_info
+         WHERE {filter}"
+    )
+}
+
+// after rollback, recompute active bindings of froms whose active binding was dropped
+fn restore_active() -> String {
+    format!(
+        "INSERT INTO active_binding (from_addr, to_addr, timestamp, height, tx_index)
+         SELECT from_addr, to_addr, timestamp, height, tx_index FROM ({}) AS ranked
+         WHERE rank = 1",
+        ranked_bindings("from_addr NOT IN (SELECT from_addr FROM active_binding)")
+    )
+}
+
+// binding of from in effect, from the materialized table
+fn select_current() -> String {
+    format!(
+        "SELECT {BIND_COLUMNS}
+         FROM bind_info
+         WHERE (from_addr, to_addr, timestamp) IN (
+             SELECT from_addr, to_addr, timestamp FROM active_binding WHERE from_addr = $1
+         )"
+    )
+}
+
+// binding of from in effect at height $2
+fn select_current_at() -> String {
+    format!(
+        "SELECT {BIND_COLUMNS} FROM ({}) AS ranked WHERE rank = 1",
+        ranked_bindings("from_addr = $1 AND height <= $2")
+    )
+}
+
+// froms whose binding in effect is to, from the materialized table. bindings within
+// heights $2 ..= $3, a page of $6 before ($4, $5)
+fn select_active_by_to() -> String {
+    format!(
+        "SELECT {BIND_COLUMNS}
+         FROM bind_info
+         WHERE (from_addr, to_addr, timestamp) IN (
+             SELECT from_addr, to_addr, timestamp FROM active_binding
+             WHERE to_addr = $1 AND height BETWEEN $2 AND $3 AND (height, tx_index) < ($4, $5)
+         )
+         ORDER BY height DESC, tx_index DESC
+         LIMIT $6"
+    )
+}
+
+const COUNT_ACTIVE_BY_TO: &str =
+    "SELECT COUNT(*) FROM active_binding WHERE to_addr = $1 AND height BETWEEN $2 AND $3";
+
+// bindings in effect at height $2 of the froms that ever bound to
+fn ranked_by_to() -> String {
+    ranked_bindings(
+        "from_addr IN (SELECT from_addr FROM bind_info WHERE to_addr = $1) AND height <= $2",
+    )
+}
+
+// froms whose binding in effect at height $2 is to. bindings within heights $3 ..= $4, a
+// page of $7 before ($5, $6)
+fn select_active_by_to_at() -> String {
+    format!(
+        "SELECT {BIND_COLUMNS} FROM ({}) AS ranked
+         WHERE rank = 1 AND to_addr = $1 AND height BETWEEN $3 AND $4
+            AND (height, tx_index) < ($5, $6)
+         ORDER BY height DESC, tx_index DESC
+         LIMIT $7",
+        ranked_by_to()
+    )
+}
+
+fn count_active_by_to_at() -> String {
+    format!(
+        "SELECT COUNT(*) FROM ({}) AS ranked
+         WHERE rank = 1 AND to_addr = $1 AND height BETWEEN $3 AND $4",
+        ranked_by_to()
+    )
+}
+
+// froms whose binding in effect is one of the tos in the batch, given by address or lock hash,
+// from the materialized table. at most $2 of each to, $1 is at or above the indexed height
+fn select_active_by_tos(n: usize) -> String {
+    let tos = batch_params(3, n);
+    newest_per(
+        "to_addr",
+        &format!(
+            "SELECT * FROM bind_info
+             WHERE height <= $1 AND (to_addr IN ({tos}) OR to_lock_hash IN ({tos}))
+                AND (from_addr, to_addr, timestamp) IN (
+                    SELECT from_addr, to_addr, timestamp FROM active_binding
+                )"
+        ),
+    )
+}
+
+// froms whose binding in effect at height $1 is one of the tos in the batch, at most $2 of
+// each to
+fn select_active_by_tos_at(n: usize) -> String {
+    let tos = batch_params(3, n);
+    let to_filter = format!("(to_addr IN ({tos}) OR to_lock_hash IN ({tos}))");
+    newest_per(
+        "to_addr",
+        &format!(
+            "SELECT * FROM ({}) AS ranked WHERE rank = 1 AND {to_filter}",
+            ranked_bindings(&format!(
+                "from_addr IN (SELECT from_addr FROM bind_info WHERE {to_filter}) AND height <= $1"
+            ))
+        ),
+    )
+}
+
+const SELECT_REJECTED_BY_TO: &str = "SELECT tx_hash, height, tx_index, from_addr, reason, message
+     FROM rejected_bind
+     WHERE to_addr = $1
+     ORDER BY height DESC, tx_index DESC";
+
+// addresses indexed before their lock was recorded
+const SELECT_UNHASHED: &str = "SELECT from_addr FROM bind_info WHERE from_lock_hash IS NULL
+     UNION SELECT to_addr FROM bind_info WHERE to_lock_hash IS NULL
+     UNION SELECT to_addr FROM rejected_bind WHERE to_lock_hash IS NULL";
+
+const UPDATE_FROM_LOCK: &str = "UPDATE bind_info SET from_lock = $2, from_lock_hash = $3 WHERE from_addr = $1 AND from_lock_hash IS NULL";
+
+const UPDATE_TO_LOCK: &str = "UPDATE bind_info SET to_lock = $2, to_lock_hash = $3 WHERE to_addr = $1 AND to_lock_hash IS NULL";
+
+const UPDATE_REJECTED_LOCK: &str =
+    "UPDATE rejected_bind SET to_lock_hash = $2 WHERE to_addr = $1 AND to_lock_hash IS NULL";
+
+// (lock hash, address) of the lock hashes in the batch
+fn select_addresses_by_lock_hash(n: usize) -> String {
+    let hashes = batch_params(1, n);
+    format!(
+        "SELECT from_lock_hash, from_addr FROM bind_info WHERE from_lock_hash IN ({hashes})
+         UNION SELECT to_lock_hash, to_addr FROM bind_info WHERE to_lock_hash IN ({hashes})
+         UNION SELECT to_lock_hash, to_addr FROM rejected_bind WHERE to_lock_hash IN ({hashes})"
+    )
+}
+
+const SELECT_DIGEST: &str = "SELECT tx_hash FROM bind_info WHERE from_addr = $1 AND digest = $2";
+
+const SELECT_LATEST_TIMESTAMP: &str = "SELECT MAX(timestamp) FROM bind_info WHERE from_addr = $1";
+
+const SELECT_HEIGHT_AT_TIMESTAMP: &str = "SELECT MAX(height) FROM block_info WHERE timestamp <= $1";
+
+// blocks indexed before timestamps were recorded, their timestamp is unknown
+const SELECT_UNTIMED_ABOVE: &str =
+    "SELECT COUNT(*) FROM block_info WHERE timestamp IS NULL AND height > $1";
+
+const SELECT_LAST_BLOCK: &str = "SELECT s.height, b.hash
+     FROM sync_status s LEFT JOIN block_info b ON b.height = s.height
+     ORDER BY s.height DESC LIMIT 1";
+
+#[derive(Debug, FromRow)]
+pub struct BindRow {
+    pub from_addr: String,
+    pub to_addr: String,
+    pub timestamp: i64,
+    pub height: i64,
+    pub tx_index: i32,
+    // provenance, null for rows indexed before it was recorded
+    pub tx_hash: Option<String>,
+    pub block_hash: Option<String>,
+    pub from_lock: Option<Vec<u8>>,
+    pub from_lock_hash: Option<String>,
+    pub to_lock: Option<Vec<u8>>,
+    pub to_lock_hash: Option<String>,
+    pub sig_scheme: Option<String>,
+    pub payload: Option<Vec<u8>>,
+    pub digest: Option<String>,
+}
+
+#[derive(Debug, FromRow)]
+pub struct RejectedRow {
+    pub tx_hash: String,
+    pub height: i64,
+    pub tx_index: i32,
+    pub from_addr: Option<String>,
+    pub reason: String,
+    pub message: String,
+}
+
+// verified binding of a block, with its position in the chain
+#[derive(Debug)]
+pub struct IndexedBind {
+    pub record: BindRecord,
+    pub tx_index: usize,
+    pub tx_hash: String,
+}
+
+// bind tx that failed verification, kept so users can be told why
+#[derive(Debug)]
+pub struct RejectedBind {
+    pub tx_hash: String,
+    pub tx_index: usize,
+    pub to: String,
+    pub to_lock: packed::Script,
+    // known only if signature was verified
+    pub from: Option<String>,
+    pub reason: RejectReason,
+    pub message: String,
+}
+
+// which bindings of a query to return, newest first
+#[derive(Debug, Clone, Copy)]
+pub struct Page {
+    // resolve bindings as of this height
+    pub at_height: i64,
+    // nothing above at_height is indexed, so active_binding answers for it
+    pub latest: bool,
+    // only bindings included within these heights
+    pub min_height: i64,
+    pub max_height: i64,
+    // only bindings before this (height, tx_index)
+    pub before: Option<(i64, i32)>,
+    pub limit: i64,
+}
+
+impl Page {
+    // every binding as of height
+    pub const fn at(height: i64) -> Self {
+        Self {
+            at_height: height,
+            latest: height == i64::MAX,
+            min_height: 0,
+            max_height: i64::MAX,
+            before: None,
+            limit: i64::MAX,
+        }
+    }
+
+    const fn before(&self) -> (i64, i32) {
+        match self.before {
+            Some(before) => before,
+            None => (i64::MAX, i32::MAX),
+        }
+    }
+}
+
+// everything the indexer writes for one block
+#[derive(Debug)]
+pub struct IndexedBlock {
+    pub height: u64,
+    pub hash: String,
+    pub parent_hash: String,
+    // block timestamp in milliseconds
+    pub timestamp: u64,
+    pub bindings: Vec<IndexedBind>,
+    pub rejected: Vec<RejectedBind>,
+}
+
+#[async_trait]
+pub trait Storage: Send + Sync + std::fmt::Debug {
+    // apply pending schema migrations of the backend
+    async fn migrate(&self) -> Result<()>;
+
+    // whether the database answers at all
+    async fn ping(&self) -> bool;
+
+    // last fully committed height
+    async fn sync_height(&self) -> Result<Option<u64>>;
+
+    // last committed height with its block hash, if recorded
+    async fn last_block(&self) -> Result<Option<(u64, Option<String>)>>;
+
+    // hash of the indexed block at height
+    async fn block_hash(&self, height: u64) -> Result<Option<String>>;
+
+    // write bindings, rejections, block record and sync checkpoint atomically
+    async fn commit_block(&self, block: &IndexedBlock) -> Result<()>;
+
+    // drop everything indexed above ancestor and move the checkpoint back
+    async fn rollback(&self, ancestor: u64) -> Result<()>;
+
+    // bindings signed by from, newest first
+    async fn bindings_by_from(&self, from: &str, page: &Page) -> Result<Vec<BindRow>>;
+
+    // number of bindings_by_from regardless of before and limit
+    async fn count_by_from(&self, from: &str, page: &Page) -> Result<u64>;
+
+    // binding of from in effect at max_height: latest signed timestamp, then chain order.
+    // i64::MAX reads active_binding
+    async fn current_binding(&self, from: &str, max_height: i64) -> Result<Option<BindRow>>;
+
+    // bindings of the froms whose binding in effect at at_height is to, newest first.
+    // read from active_binding if the page is latest
+    async fn bindings_by_to(&self, to: &str, page: &Page) -> Result<Vec<BindRow>>;
+
+    // number of bindings_by_to regardless of before and limit
+    async fn count_by_to(&self, to: &str, page: &Page) -> Result<u64>;
+
+    // the newest limit bindings_by_from of each of many froms in one query, ordered by from.
+    // a from may be given by its lock hash
+    async fn bindings_by_froms(
+        &self,
+        froms: &[String],
+        max_height: i64,
+        limit: i64,
+    ) -> Result<Vec<BindRow>>;
+
+    // the newest limit bindings_by_to of each of many tos in one query, ordered by to.
+    // a to may be given by its lock hash, i64::MAX reads active_binding
+    async fn bindings_by_tos(
+        &self,
+        tos: &[String],
+        max_height: i64,
+        limit: i64,
+    ) -> Result<Vec<BindRow>>;
+
+    async fn rejected_by_to(&self, to: &str) -> Result<Vec<RejectedRow>>;
+
+    // addresses of rows without lock, indexed before it was recorded
+    async fn unhashed_addresses(&self) -> Result<Vec<String>>;
+
+    // record lock of address where it is missing
+    async fn set_address_lock(&self, address: &str, lock: &[u8], lock_hash: &str) -> Result<()>;
+
+    // (lock hash, address) of the bound locks among lock_hashes
+    async fn addresses_by_lock_hash(&self, lock_hashes: &[String])
+    -> Result<Vec<(String, String)>>;
+
+    // hash of the tx that already bound the BindInfo of digest for from, if any
+    async fn digest_consumed_by(&self, from: &str, digest: &str) -> Result<Option<String>>;
+
+    // signed timestamp of the latest binding of from
+    async fn latest_timestamp(&self, from: &str) -> Result<Option<u64>>;
+
+    // height of the last indexed block with timestamp not after timestamp, -1 if none.
+    // None if it can't be told because a later block was indexed without timestamp
+    async fn height_at_timestamp(&self, timestamp: u64) -> Result<Option<i64>>;
+}
+
+// select backend by url scheme, sqlite:... or postgres://...
+pub async fn connect(db_url: &str) -> Result<Arc<dyn Storage>> {
+    if db_url.starts_with("sqlite:") {
+        Ok(Arc::new(sqlite::connect(db_url).await?))
+    } else {
+        Ok(Arc::new(postgres::connect(db_url).await?))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use ckb_types::{core::ScriptHashType, packed, prelude::*};
+
+    pub(crate) fn record(from: &str, to: &str, timestamp: u64) -> BindRecord {
+        let lock = |address: &str| {
+            packed::Script::new_builder()
+                .hash_type(ScriptHashType::Type.into())
+                .args(address.as_bytes().pack())
+                .build()
+        };
+        BindRecord {
+            from: from.to_string(),
+            to: to.to_string(),
+            timestamp,
+            from_lock: lock(from),
+            to_lock: lock(to),
+            sig_scheme: "Neuron".to_string(),
+            payload: vec![1, 2, 3],
+            version: 1,
+            digest: ckb_hash::blake2b_256(format!("{from}{to}{timestamp}")),
+        }
+    }
+
+    pub(crate) fn block(height: u64, bindings: Vec<(BindRecord, usize)>) -> IndexedBlock {
+        IndexedBlock {
+            height,
+            hash: format!("0x{height:064x}"),
+            parent_hash: format!("0x{:064x}", height - 1),
+            timestamp: height * 1000,
+            bindings: bindings
+                .into_iter()
+                .map(|(record, tx_index)| IndexedBind {
+                    tx_hash: format!("0x{height:032x}{tx_index:032x}"),
+                    record,
+                    tx_index,
+                })
+                .collect(),
+            rejected: vec![],
+        }
+    }
+
+    // same scenario as the postgres sql tests, through the storage trait
+    pub(super) async fn check_storage(storage: &dyn Storage) -> Result<()> {
+        storage.migrate().await?;
+        assert!(storage.ping().await);
+        assert_eq!(storage.sync_height().await?, None);
+
+        storage
+            .commit_block(&block(88, vec![(record("F2", "T1", 4), 7)]))
+            .await?;
+        storage
+            .commit_block(&block(
+                100,
+                vec![(record("F2", "T1", 5), 2), (record("F2", "T1", 6), 6)],
+            ))
+            .await?;
+        storage
+            .commit_block(&block(101, vec![(record("F1", "T1", 0), 3)]))
+            .await?;
+        storage
+            .commit_block(&block(102, vec![(record("F1", "T1", 3), 9)]))
+            .await?;
+        let mut latest = block(
+            103,
+            vec![(record("F1", "T1", 1), 1), (record("F1", "T1", 2), 5)],
+        );
+        latest.rejected.push(RejectedBind {
+            tx_hash: "0xbad".to_string(),
+            tx_index: 7,
+            to: "T1".to_string(),
+            to_lock: record("T1", "T1", 0).to_lock,
+            from: None,
+            reason: RejectReason::InvalidSignature,
+            message: "invalid signature".to_string(),
+        });
+        // the to of a rejected tx only
+        latest.rejected.push(RejectedBind {
+            tx_hash: "0xbad2".to_string(),
+            tx_index: 8,
+            to: "R1".to_string(),
+            to_lock: record("R1", "R1", 0).to_lock,
+            from: None,
+            reason: RejectReason::InvalidSignature,
+            message: "invalid signature".to_string(),
+        });
+        storage.commit_block(&latest).await?;
+
+        assert_eq!(storage.sync_height().await?, Some(103));
+        assert_eq!(
+            storage.last_block().await?,
+            Some((103, Some(format!("0x{:064x}", 103))))
+        );
+        assert_eq!(
+            storage.block_hash(100).await?,
+            Some(format!("0x{:064x}", 100))
+        );
+
+        let rows = storage.bindings_by_from("F1", &Page::at(i64::MAX)).await?;
+        assert_eq!(rows.len(), 4);
+        assert_eq!((rows[0].height, rows[0].tx_index), (103, 5));
+        assert_eq!(rows[0].payload.as_deref(), Some(&[1, 2, 3][..]));
+
+        let mut rows = storage.bindings_by_to("T1", &Page::at(i64::MAX)).await?;
+        rows.sort_by(|a, b| a.from_addr.cmp(&b.from_addr));
+        let latest: Vec<_> = rows
+            .iter()
+            .map(|row| (row.from_addr.as_str(), row.height, row.tx_index))
+            .collect();
+        // the latest signed timestamp wins over chain order
+        assert_eq!(latest, vec![("F1", 102, 9), ("F2", 100, 6)]);
+
+        let rows = storage.bindings_by_to("T1", &Page::at(100)).await?;
+        assert_eq!(rows.len(), 1);
+        assert_eq!((rows[0].height, rows[0].tx_index), (100, 6));
+
+        // F2 binds to T2, F3 binds twice with one timestamp, the later tx wins
+        storage
+            .commit_block(&block(
+                104,
+                vec![
+                    (record("F2", "T2", 7), 1),
+                    (record("F3", "T2", 8), 2),
+                    (record("F3", "T1", 8), 3),
+                ],
+            ))
+            .await?;
+        let current = |from: &'static str, max_height: i64| async move {
+            Ok::<_, color_eyre::Report>(
+                storage
+                    .current_binding(from, max_height)
+                    .await?
+                    .map(|row| row.to_addr),
+            )
+        };
+        assert_eq!(current("F2", i64::MAX).await?.as_deref(), Some("T2"));
+        assert_eq!(current("F2", 103).await?.as_deref(), Some("T1"));
+        assert_eq!(current("F3", i64::MAX).await?.as_deref(), Some("T1"));
+        assert_eq!(current("F3", 103).await?, None);
+        let froms = |rows: Vec<BindRow>| {
+            rows.into_iter()
+                .map(|row| row.from_addr)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            froms(storage.bindings_by_to("T1", &Page::at(i64::MAX)).await?),
+            ["F3", "F1"]
+        );
+        assert_eq!(
+            froms(storage.bindings_by_to("T1", &Page::at(104)).await?),
+            ["F3", "F1"]
+        );
+        assert_eq!(
+            froms(storage.bindings_by_to("T1", &Page::at(103)).await?),
+            ["F1", "F2"]
+        );
+        assert_eq!(
+            froms(storage.bindings_by_to("T2", &Page::at(i64::MAX)).await?),
+            ["F2"]
+        );
+
+        // pages walk the bindings newest first, counts ignore the page position. pinned at the
+        // indexed height they read active_binding, and the ranked query agrees
+        for (at_height, latest) in [(i64::MAX, true), (104, true), (104, false)] {
+            let mut page = Page {
+                latest,
+                limit: 1,
+                ..Page::at(at_height)
+            };
+            let first = storage.bindings_by_to("T1", &page).await?;
+            assert_eq!(froms(first), ["F3"]);
+            page.before = Some((104, 3));
+            let second = storage.bindings_by_to("T1", &page).await?;
+            assert_eq!(froms(second), ["F1"]);
+            assert_eq!(storage.count_by_to("T1", &page).await?, 2);
+            page.max_height = 103;
+            assert_eq!(storage.count_by_to("T1", &page).await?, 1);
+        }
+        let page = Page {
+            min_height: 101,
+            max_height: 102,
+            before: Some((102, 9)),
+            ..Page::at(i64::MAX)
+        };
+        let rows = storage.bindings_by_from("F1", &page).await?;
+        assert_eq!(
+            rows.iter()
+                .map(|row| (row.height, row.tx_index))
+                .collect::<Vec<_>>(),
+            [(101, 3)]
+        );
+        assert_eq!(storage.count_by_from("F1", &page).await?, 2);
+        assert_eq!(storage.count_by_from("F1", &Page::at(102)).await?, 2);
+
+        // batches answer like one query per address
+        let batch = |addrs: &[&str]| {
+            addrs
+                .iter()
+                .map(|addr| addr.to_string())
+                .collect::<Vec<_>>()
+        };
+        let rows = storage
+            .bindings_by_froms(&batch(&["F2", "F1", "F9"]), i64::MAX, i64::MAX)
+            .await?;
+        let mut expected = storage.bindings_by_from("F1", &Page::at(i64::MAX)).await?;
+        expected.extend(storage.bindings_by_from("F2", &Page::at(i64::MAX)).await?);
+        let key = |row: &BindRow| (row.from_addr.clone(), row.height, row.tx_index);
+        assert_eq!(
+            rows.iter().map(key).collect::<Vec<_>>(),
+            expected.iter().map(key).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            storage
+                .bindings_by_froms(&batch(&["F1"]), 101, i64::MAX)
+                .await?
+                .len(),
+            1
+        );
+        // at most limit of each address, the newest, and addresses may be lock hashes
+        let lock_hash = |address: &str| {
+            let lock = record(address, address, 0).from_lock;
+            format!("{:#x}", lock.calc_script_hash())
+        };
+        let rows = storage
+            .bindings_by_froms(&batch(&[&lock_hash("F1"), "F2"]), i64::MAX, 1)
+            .await?;
+        assert_eq!(
+            rows.iter().map(key).collect::<Vec<_>>(),
+            [&expected[0], &expected[4]].map(key)
+        );
+        let tos = |rows: Vec<BindRow>| {
+            rows.into_iter()
+                .map(|row| (row.to_addr, row.from_addr))
+                .collect::<Vec<_>>()
+        };
+        let t = |to: &str, from: &str| (to.to_string(), from.to_string());
+        assert_eq!(
+            tos(storage
+                .bindings_by_tos(&batch(&["T2", "T1"]), i64::MAX, i64::MAX)
+                .await?),
+            [t("T1", "F3"), t("T1", "F1"), t("T2", "F2")]
+        );
+        assert_eq!(
+            tos(storage
+                .bindings_by_tos(&batch(&["T2", "T1"]), 103, i64::MAX)
+                .await?),
+            [t("T1", "F1"), t("T1", "F2")]
+        );
+        for (at_height, from) in [(i64::MAX, "F3"), (103, "F1")] {
+            assert_eq!(
+                tos(storage
+                    .bindings_by_tos(&batch(&[&lock_hash("T1")]), at_height, 1)
+                    .await?),
+                [t("T1", from)]
+            );
+        }
+
+        // bound locks are found by their hash
+        assert!(storage.unhashed_addresses().await?.is_empty());
+        let mut addresses = storage
+            .addresses_by_lock_hash(&batch(&[
+                &lock_hash("F1"),
+                &lock_hash("T2"),
+                &lock_hash("R1"),
+                "0x00",
+            ]))
+            .await?;
+        addresses.sort();
+        let mut expected = vec![
+            (lock_hash("F1"), "F1".to_string()),
+            (lock_hash("T2"), "T2".to_string()),
+            (lock_hash("R1"), "R1".to_string()),
+        ];
+        expected.sort();
+        assert_eq!(addresses, expected);
+
+        // block timestamps resolve to the last block at or before them
+        assert_eq!(storage.height_at_timestamp(103_500).await?, Some(103));
+        assert_eq!(storage.height_at_timestamp(104_000).await?, Some(104));
+        assert_eq!(storage.height_at_timestamp(87_999).await?, Some(-1));
+
+        let digest = format!("0x{}", hex::encode(record("F1", "T1", 3).digest));
+        assert_eq!(
+            storage.digest_consumed_by("F1", &digest).await?,
+            Some(format!("0x{:032x}{:032x}", 102, 9))
+        );
+        assert_eq!(storage.digest_consumed_by("F1", "0x00").await?, None);
+        assert_eq!(storage.digest_consumed_by("F2", &digest).await?, None);
+        assert_eq!(storage.latest_timestamp("F1").await?, Some(3));
+        assert_eq!(storage.latest_timestamp("F9").await?, None);
+
+        let rejected = storage.rejected_by_to("T1").await?;
+        assert_eq!(rejected.len(), 1);
+        assert_eq!(rejected[0].reason, "invalid_signature");
+
+        storage.rollback(101).await?;
+        assert_eq!(storage.sync_height().await?, Some(101));
+        assert_eq!(current("F1", i64::MAX).await?.as_deref(), Some("T1"));
+        assert_eq!(current("F2", i64::MAX).await?.as_deref(), Some("T1"));
+        assert_eq!(current("F3", i64::MAX).await?, None);
+        assert_eq!(storage.block_hash(102).await?, None);
+        assert_eq!(
+            storage
+                .bindings_by_from("F1", &Page::at(i64::MAX))
+                .await?
+                .len(),
+            1
+        );
+        assert!(storage.rejected_by_to("T1").await?.is_empty());
+        assert_eq!(storage.digest_consumed_by("F1", &digest).await?, None);
+        assert_eq!(storage.latest_timestamp("F1").await?, Some(0));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_storage() -> Result<()> {
+        let storage = connect("sqlite::memory:").await?;
+        check_storage(storage.as_ref()).await
+    }
+}
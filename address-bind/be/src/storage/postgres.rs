@@ -0,0 +1,187 @@
+use super::SqlStorage;
+use color_eyre::Result;
+use sqlx::{any::AnyPoolOptions, migrate::Migrator};
+
+// versioned schema, embedded at build time from ./migrations/postgres
+static MIGRATOR: Migrator = sqlx::migrate!("./migrations/postgres");
+
+pub async fn connect(db_url: &str) -> Result<SqlStorage> {
+    sqlx::any::install_default_drivers();
+    Ok(SqlStorage::new(
+        AnyPoolOptions::new()
+            .max_connections(5)
+            .connect(db_url)
+            .await?,
+        &MIGRATOR,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::{Executor, Pool, Postgres, postgres::PgPoolOptions, query, query_as};
+
+    async fn get_test_db() -> Option<Pool<Postgres>> {
+        let db_url = std::env::var("TEST_DB_URL")
+            .or_else(|_| std::env::var("DB_URL"))
+            .ok()?;
+        PgPoolOptions::new()
+            .max_connections(5)
+            .connect(&db_url)
+            .await
+            .ok()
+    }
+
+    fn test_schema(suffix: &str) -> String {
+        format!("abtest_{}_{}", std::process::id(), suffix)
+    }
+
+    async fn setup_schema(db: &Pool<Postgres>, s: &str) -> Result<()> {
+        sqlx::query(&format!("CREATE SCHEMA IF NOT EXISTS {s}"))
+            .execute(db)
+            .await?;
+        sqlx::query(&format!(
+            "CREATE TABLE IF NOT EXISTS {s}.sync_status (height BIGINT PRIMARY KEY)"
+        ))
+        .execute(db)
+        .await?;
+        sqlx::query(&format!("CREATE TABLE IF NOT EXISTS {s}.bind_info (from_addr TEXT, to_addr TEXT, timestamp BIGINT, height BIGINT, tx_index INTEGER, UNIQUE(from_addr, to_addr, timestamp))")).execute(db).await?;
+        sqlx::query(&format!("TRUNCATE TABLE {s}.bind_info RESTART IDENTITY"))
+            .execute(db)
+            .await?;
+        sqlx::query(&format!("TRUNCATE TABLE {s}.sync_status RESTART IDENTITY"))
+            .execute(db)
+            .await?;
+        Ok(())
+    }
+
+    async fn seed_data(db: &Pool<Postgres>, s: &str) -> Result<()> {
+        // F1 -> T1 with multiple heights and tx_index
+        db.execute(query(&format!("INSERT INTO {s}.bind_info (from_addr, to_addr, timestamp, height, tx_index) VALUES ($1, $2, $3, $4, $5)"))
+            .bind("F1").bind("T1").bind(0_i64).bind(101_i64).bind(3_i32)).await?;
+        db.execute(query(&format!("INSERT INTO {s}.bind_info (from_addr, to_addr, timestamp, height, tx_index) VALUES ($1, $2, $3, $4, $5)"))
+            .bind("F1").bind("T1").bind(1_i64).bind(103_i64).bind(1_i32)).await?;
+        db.execute(query(&format!("INSERT INTO {s}.bind_info (from_addr, to_addr, timestamp, height, tx_index) VALUES ($1, $2, $3, $4, $5)"))
+            .bind("F1").bind("T1").bind(2_i64).bind(103_i64).bind(5_i32)).await?; // latest by height then tx_index
+        db.execute(query(&format!("INSERT INTO {s}.bind_info (from_addr, to_addr, timestamp, height, tx_index) VALUES ($1, $2, $3, $4, $5)"))
+            .bind("F1").bind("T1").bind(3_i64).bind(102_i64).bind(9_i32)).await?;
+
+        // F2 -> T1
+        db.execute(query(&format!("INSERT INTO {s}.bind_info (from_addr, to_addr, timestamp, height, tx_index) VALUES ($1, $2, $3, $4, $5)"))
+            .bind("F2").bind("T1").bind(4_i64).bind(88_i64).bind(7_i32)).await?;
+        db.execute(query(&format!("INSERT INTO {s}.bind_info (from_addr, to_addr, timestamp, height, tx_index) VALUES ($1, $2, $3, $4, $5)"))
+            .bind("F2").bind("T1").bind(5_i64).bind(100_i64).bind(2_i32)).await?;
+        db.execute(query(&format!("INSERT INTO {s}.bind_info (from_addr, to_addr, timestamp, height, tx_index) VALUES ($1, $2, $3, $4, $5)"))
+            .bind("F2").bind("T1").bind(6_i64).bind(100_i64).bind(6_i32)).await?; // latest by height then tx_index
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_query_by_to_distinct_on() -> Result<()> {
+        let Some(db) = get_test_db().await else {
+            eprintln!("Skipped test_query_by_to_distinct_on: TEST_DB_URL/DB_URL not set");
+            return Ok(());
+        };
+        let s = test_schema("to");
+        setup_schema(&db, &s).await?;
+        seed_data(&db, &s).await?;
+
+        let sql = format!(
+            "SELECT DISTINCT ON (from_addr) from_addr, height, tx_index
+                           FROM {s}.bind_info
+                           WHERE to_addr = $1
+                           ORDER BY from_addr, height DESC, tx_index DESC"
+        );
+        let rows: Vec<(String, i64, i32)> = query_as(&sql).bind("T1").fetch_all(&db).await?;
+
+        // Expect F1->height 103, tx_index=5, F2->height 100, tx_index=6
+        let mut map = std::collections::HashMap::new();
+        for (from, height, tx_index) in rows {
+            map.insert(from, (height, tx_index));
+        }
+        assert_eq!(map.get("F1"), Some(&(103_i64, 5_i32)));
+        assert_eq!(map.get("F2"), Some(&(100_i64, 6_i32)));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_query_by_from_ordering() -> Result<()> {
+        let Some(db) = get_test_db().await else {
+            eprintln!("Skipped test_query_by_from_ordering: TEST_DB_URL/DB_URL not set");
+            return Ok(());
+        };
+        let s = test_schema("from");
+        setup_schema(&db, &s).await?;
+        seed_data(&db, &s).await?;
+
+        let sql = format!(
+            "SELECT to_addr, height, tx_index
+                           FROM {s}.bind_info
+                           WHERE from_addr = $1
+                           ORDER BY height DESC, tx_index DESC"
+        );
+        let rows: Vec<(String, i64, i32)> = query_as(&sql).bind("F1").fetch_all(&db).await?;
+
+        // First row should be height=103, tx_index=5
+        assert!(!rows.is_empty());
+        let (to, height, tx_index) = &rows[0];
+        assert_eq!(to, "T1");
+        assert_eq!(*height, 103_i64);
+        assert_eq!(*tx_index, 5_i32);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_query_by_to_at_height_cutoff() -> Result<()> {
+        let Some(db) = get_test_db().await else {
+            eprintln!("Skipped test_query_by_to_at_height_cutoff: TEST_DB_URL/DB_URL not set");
+            return Ok(());
+        };
+        let s = test_schema("toheight");
+        setup_schema(&db, &s).await?;
+        seed_data(&db, &s).await?;
+
+        // Same data as before; cut off at height <= 100
+        let sql = format!(
+            "SELECT DISTINCT ON (from_addr) from_addr, height, tx_index
+                         FROM {s}.bind_info
+                         WHERE to_addr = $1 AND height <= $2
+                         ORDER BY from_addr, height DESC, tx_index DESC"
+        );
+        let rows: Vec<(String, i64, i32)> = query_as(&sql)
+            .bind("T1")
+            .bind(100_i64)
+            .fetch_all(&db)
+            .await?;
+
+        // Expect F1 excluded (latest at 103), F2 included with height 100, tx_index=6
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].0, "F2");
+        assert_eq!(rows[0].1, 100_i64);
+        assert_eq!(rows[0].2, 6_i32);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_pg_storage() -> Result<()> {
+        let Some(db) = get_test_db().await else {
+            eprintln!("Skipped test_pg_storage: TEST_DB_URL/DB_URL not set");
+            return Ok(());
+        };
+        let s = test_schema("storage");
+        db.execute(query(&format!("DROP SCHEMA IF EXISTS {s} CASCADE")))
+            .await?;
+        db.execute(query(&format!("CREATE SCHEMA {s}"))).await?;
+
+        // run migrations and queries inside the test schema only
+        let db_url = std::env::var("TEST_DB_URL").or_else(|_| std::env::var("DB_URL"))?;
+        let separator = if db_url.contains('?') { '&' } else { '?' };
+        let storage = connect(&format!("{db_url}{separator}options[search_path]={s}")).await?;
+        let ret = crate::storage::tests::check_storage(&storage).await;
+
+        db.execute(query(&format!("DROP SCHEMA {s} CASCADE")))
+            .await?;
+        ret
+    }
+}
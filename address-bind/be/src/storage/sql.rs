@@ -0,0 +1,330 @@
+use super::{
+    BindRow, COUNT_ACTIVE_BY_TO, COUNT_BY_FROM, INSERT_BIND, INSERT_REJECTED, IndexedBlock, Page,
+    RejectedRow, SELECT_DIGEST, SELECT_HEIGHT_AT_TIMESTAMP, SELECT_LAST_BLOCK,
+    SELECT_LATEST_TIMESTAMP, SELECT_REJECTED_BY_TO, SELECT_UNHASHED, SELECT_UNTIMED_ABOVE, Storage,
+    UPDATE_FROM_LOCK, UPDATE_REJECTED_LOCK, UPDATE_TO_LOCK, UPSERT_ACTIVE, UPSERT_BLOCK,
+    count_active_by_to_at, restore_active, select_active_by_to, select_active_by_to_at,
+    select_active_by_tos, select_active_by_tos_at, select_addresses_by_lock_hash, select_by_from,
+    select_by_froms, select_current, select_current_at,
+};
+use async_trait::async_trait;
+use ckb_types::prelude::Entity;
+use color_eyre::Result;
+use sqlx::{AnyConnection, AnyPool, migrate::Migrator, query, query_as};
+
+// storage on any sql backend, postgres and sqlite differ only in pool and migrations
+#[derive(Debug, Clone)]
+pub struct SqlStorage {
+    db: AnyPool,
+    migrator: &'static Migrator,
+}
+
+impl SqlStorage {
+    pub const fn new(db: AnyPool, migrator: &'static Migrator) -> Self {
+        Self { db, migrator }
+    }
+}
+
+// sync_status keeps a single row, the last fully committed height
+async fn set_sync_height(tx: &mut AnyConnection, height: u64) -> Result<()> {
+    query("DELETE FROM sync_status").execute(&mut *tx).await?;
+    query("INSERT INTO sync_status (height) VALUES ($1)")
+        .bind(height as i64)
+        .execute(&mut *tx)
+        .await?;
+    Ok(())
+}
+
+#[async_trait]
+impl Storage for SqlStorage {
+    async fn migrate(&self) -> Result<()> {
+        self.migrator.run(&self.db).await?;
+        Ok(())
+    }
+
+    async fn ping(&self) -> bool {
+        query("SELECT 1").execute(&self.db).await.is_ok()
+    }
+
+    async fn sync_height(&self) -> Result<Option<u64>> {
+        let height: Option<(i64,)> =
+            query_as("SELECT height FROM sync_status ORDER BY height DESC LIMIT 1")
+                .fetch_optional(&self.db)
+                .await?;
+        Ok(height.map(|(height,)| height as u64))
+    }
+
+    async fn last_block(&self) -> Result<Option<(u64, Option<String>)>> {
+        let block: Option<(i64, Option<String>)> =
+            query_as(SELECT_LAST_BLOCK).fetch_optional(&self.db).await?;
+        Ok(block.map(|(height, hash)| (height as u64, hash)))
+    }
+
+    async fn block_hash(&self, height: u64) -> Result<Option<String>> {
+        let hash: Option<(String,)> = query_as("SELECT hash FROM block_info WHERE height = $1")
+            .bind(height as i64)
+            .fetch_optional(&self.db)
+            .await?;
+        Ok(hash.map(|(hash,)| hash))
+    }
+
+    async fn commit_block(&self, block: &IndexedBlock) -> Result<()> {
+        let mut tx = self.db.begin().await?;
+        for bind in &block.bindings {
+            let record = &bind.record;
+            query(INSERT_BIND)
+                .bind(&record.from)
+                .bind(&record.to)
+                .bind(record.timestamp as i64)
+                .bind(block.height as i64)
+                .bind(bind.tx_index as i32)
+                .bind(&bind.tx_hash)
+                .bind(&block.hash)
+                .bind(record.from_lock.as_slice())
+                .bind(format!("{:#x}", record.from_lock.calc_script_hash()))
+                .bind(record.to_lock.as_slice())
+                .bind(format!("{:#x}", record.to_lock.calc_script_hash()))
+                .bind(&record.sig_scheme)
+                .bind(&record.payload)
+                .bind(format!("0x{}", hex::encode(record.digest)))
+                .execute(&mut *tx)
+                .await?;
+            query(UPSERT_ACTIVE)
+                .bind(&record.from)
+                .bind(&record.to)
+                .bind(record.timestamp as i64)
+                .bind(block.height as i64)
+                .bind(bind.tx_index as i32)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        for rejected in &block.rejected {
+            query(INSERT_REJECTED)
+                .bind(&rejected.tx_hash)
+                .bind(block.height as i64)
+                .bind(rejected.tx_index as i32)
+                .bind(&rejected.to)
+                .bind(&rejected.from)
+                .bind(rejected.reason.as_str())
+                .bind(&rejected.message)
+                .bind(format!("{:#x}", rejected.to_lock.calc_script_hash()))
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        // record processed block for reorg detection
+        query(UPSERT_BLOCK)
+            .bind(block.height as i64)
+            .bind(&block.hash)
+            .bind(&block.parent_hash)
+            .bind(block.timestamp as i64)
+            .execute(&mut *tx)
+            .await?;
+
+        set_sync_height(&mut tx, block.height).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn rollback(&self, ancestor: u64) -> Result<()> {
+        let mut tx = self.db.begin().await?;
+        for table in ["bind_info", "rejected_bind", "block_info", "active_binding"] {
+            query(&format!("DELETE FROM {table} WHERE height > $1"))
+                .bind(ancestor as i64)
+                .execute(&mut *tx)
+                .await?;
+        }
+        query(&restore_active()).execute(&mut *tx).await?;
+        set_sync_height(&mut tx, ancestor).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn bindings_by_from(&self, from: &str, page: &Page) -> Result<Vec<BindRow>> {
+        let (height, tx_index) = page.before();
+        Ok(query_as(&select_by_from())
+            .bind(from)
+            .bind(page.min_height)
+            .bind(page.max_height.min(page.at_height))
+            .bind(height)
+            .bind(tx_index)
+            .bind(page.limit)
+            .fetch_all(&self.db)
+            .await?)
+    }
+
+    async fn count_by_from(&self, from: &str, page: &Page) -> Result<u64> {
+        let (count,): (i64,) = query_as(COUNT_BY_FROM)
+            .bind(from)
+            .bind(page.min_height)
+            .bind(page.max_height.min(page.at_height))
+            .fetch_one(&self.db)
+            .await?;
+        Ok(count as u64)
+    }
+
+    async fn current_binding(&self, from: &str, max_height: i64) -> Result<Option<BindRow>> {
+        if max_height == i64::MAX {
+            return Ok(query_as(&select_current())
+                .bind(from)
+                .fetch_optional(&self.db)
+                .await?);
+        }
+        Ok(query_as(&select_current_at())
+            .bind(from)
+            .bind(max_height)
+            .fetch_optional(&self.db)
+            .await?)
+    }
+
+    async fn bindings_by_to(&self, to: &str, page: &Page) -> Result<Vec<BindRow>> {
+        let (height, tx_index) = page.before();
+        if page.latest {
+            return Ok(query_as(&select_active_by_to())
+                .bind(to)
+                .bind(page.min_height)
+                .bind(page.max_height)
+                .bind(height)
+                .bind(tx_index)
+                .bind(page.limit)
+                .fetch_all(&self.db)
+                .await?);
+        }
+        Ok(query_as(&select_active_by_to_at())
+            .bind(to)
+            .bind(page.at_height)
+            .bind(page.min_height)
+            .bind(page.max_height)
+            .bind(height)
+            .bind(tx_index)
+            .bind(page.limit)
+            .fetch_all(&self.db)
+            .await?)
+    }
+
+    async fn count_by_to(&self, to: &str, page: &Page) -> Result<u64> {
+        if page.latest {
+            let (count,): (i64,) = query_as(COUNT_ACTIVE_BY_TO)
+                .bind(to)
+                .bind(page.min_height)
+                .bind(page.max_height)
+                .fetch_one(&self.db)
+                .await?;
+            return Ok(count as u64);
+        }
+        let (count,): (i64,) = query_as(&count_active_by_to_at())
+            .bind(to)
+            .bind(page.at_height)
+            .bind(page.min_height)
+            .bind(page.max_height)
+            .fetch_one(&self.db)
+            .await?;
+        Ok(count as u64)
+    }
+
+    async fn bindings_by_froms(
+        &self,
+        froms: &[String],
+        max_height: i64,
+        limit: i64,
+    ) -> Result<Vec<BindRow>> {
+        let sql = select_by_froms(froms.len());
+        let mut select = query_as(&sql).bind(max_height).bind(limit);
+        for from in froms {
+            select = select.bind(from);
+        }
+        Ok(select.fetch_all(&self.db).await?)
+    }
+
+    async fn bindings_by_tos(
+        &self,
+        tos: &[String],
+        max_height: i64,
+        limit: i64,
+    ) -> Result<Vec<BindRow>> {
+        let sql = if max_height == i64::MAX {
+            select_active_by_tos(tos.len())
+        } else {
+            select_active_by_tos_at(tos.len())
+        };
+        let mut select = query_as(&sql).bind(max_height).bind(limit);
+        for to in tos {
+            select = select.bind(to);
+        }
+        Ok(select.fetch_all(&self.db).await?)
+    }
+
+    async fn rejected_by_to(&self, to: &str) -> Result<Vec<RejectedRow>> {
+        Ok(query_as(SELECT_REJECTED_BY_TO)
+            .bind(to)
+            .fetch_all(&self.db)
+            .await?)
+    }
+
+    async fn unhashed_addresses(&self) -> Result<Vec<String>> {
+        let addresses: Vec<(String,)> = query_as(SELECT_UNHASHED).fetch_all(&self.db).await?;
+        Ok(addresses.into_iter().map(|(address,)| address).collect())
+    }
+
+    async fn set_address_lock(&self, address: &str, lock: &[u8], lock_hash: &str) -> Result<()> {
+        let mut tx = self.db.begin().await?;
+        for update in [UPDATE_FROM_LOCK, UPDATE_TO_LOCK] {
+            query(update)
+                .bind(address)
+                .bind(lock)
+                .bind(lock_hash)
+                .execute(&mut *tx)
+                .await?;
+        }
+        query(UPDATE_REJECTED_LOCK)
+            .bind(address)
+            .bind(lock_hash)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn addresses_by_lock_hash(
+        &self,
+        lock_hashes: &[String],
+    ) -> Result<Vec<(String, String)>> {
+        let sql = select_addresses_by_lock_hash(lock_hashes.len());
+        let mut select = query_as(&sql);
+        for lock_hash in lock_hashes {
+            select = select.bind(lock_hash);
+        }
+        Ok(select.fetch_all(&self.db).await?)
+    }
+
+    async fn digest_consumed_by(&self, from: &str, digest: &str) -> Result<Option<String>> {
+        let tx_hash: Option<(Option<String>,)> = query_as(SELECT_DIGEST)
+            .bind(from)
+            .bind(digest)
+            .fetch_optional(&self.db)
+            .await?;
+        Ok(tx_hash.map(|(tx_hash,)| tx_hash.unwrap_or_default()))
+    }
+
+    async fn latest_timestamp(&self, from: &str) -> Result<Option<u64>> {
+        let (timestamp,): (Option<i64>,) = query_as(SELECT_LATEST_TIMESTAMP)
+            .bind(from)
+            .fetch_one(&self.db)
+            .await?;
+        Ok(timestamp.map(|timestamp| timestamp as u64))
+    }
+
+    async fn height_at_timestamp(&self, timestamp: u64) -> Result<Option<i64>> {
+        let (height,): (Option<i64>,) = query_as(SELECT_HEIGHT_AT_TIMESTAMP)
+            .bind(timestamp as i64)
+            .fetch_one(&self.db)
+            .await?;
+        let height = height.unwrap_or(-1);
+        let (untimed,): (i64,) = query_as(SELECT_UNTIMED_ABOVE)
+            .bind(height)
+            .fetch_one(&self.db)
+            .await?;
+        Ok((untimed == 0).then_some(height))
+    }
+}
@@ -0,0 +1,41 @@
+use super::SqlStorage;
+use color_eyre::Result;
+use sqlx::{
+    any::{AnyConnectOptions, AnyPoolOptions},
+    migrate::Migrator,
+};
+use std::str::FromStr;
+
+// versioned schema, embedded at build time from ./migrations/sqlite
+static MIGRATOR: Migrator = sqlx::migrate!("./migrations/sqlite");
+
+// embedded database, for deployments without a database server.
+// sqlite://path/to/file.db or sqlite::memory:
+pub async fn connect(db_url: &str) -> Result<SqlStorage> {
+    sqlx::any::install_default_drivers();
+    let mut options = AnyConnectOptions::from_str(db_url)?;
+    let pool = if db_url.contains(":memory:") {
+        // every connection to an in-memory database opens a new empty one, keep the only one
+        AnyPoolOptions::new()
+            .max_connections(1)
+            .idle_timeout(None)
+            .max_lifetime(None)
+    } else {
+        // create the file if missing
+        if !options
+            .database_url
+            .query_pairs()
+            .any(|(key, _)| key == "mode")
+        {
+            options
+                .database_url
+                .query_pairs_mut()
+                .append_pair("mode", "rwc");
+        }
+        AnyPoolOptions::new().max_connections(5)
+    };
+    Ok(SqlStorage::new(
+        pool.connect_with(options).await?,
+        &MIGRATOR,
+    ))
+}
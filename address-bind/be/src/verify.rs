@@ -1,7 +1,11 @@
-use crate::bind::BindInfoWithSig;
-use ckb_jsonrpc_types::Either;
+use crate::chain::ChainSource;
+use crate::error::{RejectReason, VerifyError};
+use crate::message::{DEFAULT_DOMAIN, SignedBindInfo, bind_info_message};
+use crate::signature::{
+    RecoverError, ckb_message_hash, is_multisig_sig, parse_multisig_sig, parse_signature_object,
+    recover_lock_script, recover_multisig_lock, secp256k1_recover,
+};
 use ckb_jsonrpc_types::Transaction;
-use ckb_sdk::rpc::CkbRpcClient;
 use ckb_sdk::{Address, AddressPayload, NetworkType};
 use ckb_types::bytes::Bytes;
 use ckb_types::core::ScriptHashType;
@@ -9,33 +13,14 @@ use ckb_types::prelude::Entity;
 use ckb_types::prelude::Pack;
 use ckb_types::{H160, H256, packed};
 use molecule::prelude::Builder;
-use secp256k1::{Error, Message, PublicKey, ecdsa};
 use std::str::FromStr;
 
-pub async fn get_tx(ckb_client: &CkbRpcClient, tx_hash: H256) -> Result<Transaction, String> {
-    let tx_either = ckb_client
-        .get_transaction(tx_hash)
-        .map_err(|e| format!("Failed to get transaction: {e}"))?
-        .ok_or("tx not found".to_string())?
-        .transaction
-        .ok_or("tx not found".to_string())?
-        .inner;
-    match tx_either {
-        Either::Left(tx_view) => {
-            let tx = tx_view.inner;
-            Ok(tx)
-        }
-        Either::Right(_) => Err("tx not found".to_string()),
-    }
-}
-
-fn recover(msg_digest: [u8; 32], sig: [u8; 64], recovery_id: u8) -> Result<PublicKey, Error> {
-    let secp = secp256k1::Secp256k1::new();
-    let id = ecdsa::RecoveryId::try_from(i32::from(recovery_id))?;
-    let sig = ecdsa::RecoverableSignature::from_compact(&sig, id)?;
-    let msg = Message::from_digest(msg_digest);
-
-    secp.recover_ecdsa(&msg, &sig)
+pub async fn get_tx(chain: &dyn ChainSource, tx_hash: H256) -> Result<Transaction, VerifyError> {
+    chain
+        .get_transaction(&tx_hash)
+        .await
+        .map_err(|e| VerifyError::Transient(e.to_string()))?
+        .ok_or_else(|| VerifyError::Transient(format!("tx {tx_hash} not found")))
 }
 
 // from address must be secp256/blake160
@@ -58,112 +43,711 @@ pub fn calculate_address(lock_script: &packed::Script, network: NetworkType) ->
     Address::new(network, payload, true)
 }
 
+// full format address on network of the lock of address, whatever its encoding and network
+pub fn normalize_address(address: &str, network: NetworkType) -> Result<String, String> {
+    let address = Address::from_str(address)?;
+    Ok(calculate_address(&packed::Script::from(&address), network).to_string())
+}
+
+// bind info verified from a transaction
+#[derive(Debug, Clone)]
+pub struct BindRecord {
+    pub from: String,
+    pub to: String,
+    pub timestamp: u64,
+    pub from_lock: packed::Script,
+    pub to_lock: packed::Script,
+    // "Neuron" for raw signature, otherwise signType of ccc Signature Object
+    pub sig_scheme: String,
+    // raw BindInfoWithSig bytes
+    pub payload: Vec<u8>,
+    // BindInfo schema version, 1 or 2
+    pub version: u8,
+    // blake2b of the signed BindInfo, identifies the message whatever the signature
+    pub digest: [u8; 32],
+}
+
+// accepted layout of a bind tx
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ShapePolicy {
+    // any number of inputs and outputs, all of the to lock
+    #[default]
+    LockGroup,
+    // exactly one input and one output
+    Strict,
+}
+
+// settings of verify_tx besides chain and network
+#[derive(Debug, Clone)]
+pub struct VerifyOptions {
+    // recoverysig service, for signatures can not be verified natively
+    pub recovery_url: Option<String>,
+    pub shape: ShapePolicy,
+    // domain BindInfoV2 must be signed for
+    pub domain: String,
+}
+
+impl Default for VerifyOptions {
+    fn default() -> Self {
+        Self {
+            recovery_url: None,
+            shape: ShapePolicy::default(),
+            domain: DEFAULT_DOMAIN.to_string(),
+        }
+    }
+}
+
 pub async fn verify_tx(
-    ckb_client: &CkbRpcClient,
+    chain: &dyn ChainSource,
     network: NetworkType,
     tx: &Transaction,
-    recovery_url: &str,
-) -> Result<(String, String, u64), String> {
+    options: &VerifyOptions,
+) -> Result<BindRecord, VerifyError> {
     let inputs_count = tx.inputs.len();
     let outputs_count = tx.outputs.len();
 
-    // one input, one output
-    if inputs_count != 1 || outputs_count != 1 {
-        return Err("inputs_count or outputs_count not equal 1".to_string());
+    match options.shape {
+        ShapePolicy::Strict if inputs_count != 1 || outputs_count != 1 => {
+            return Err(VerifyError::NotBindTx(
+                "inputs_count or outputs_count not equal 1".to_string(),
+            ));
+        }
+        _ if inputs_count == 0 || outputs_count == 0 => {
+            return Err(VerifyError::NotBindTx("no inputs or outputs".to_string()));
+        }
+        _ => {}
     }
 
-    // extract bind info with sig from witness
-    let witness = tx.witnesses[0].clone();
+    // all inputs share the to lock, so the first witness of the lock group is witness 0
+    let witness = tx
+        .witnesses
+        .first()
+        .cloned()
+        .ok_or_else(|| VerifyError::NotBindTx("witness is None".to_string()))?;
     let witness_bytes = witness.into_bytes();
     let witness_args = packed::WitnessArgs::from_compatible_slice(&witness_bytes)
-        .map_err(|e| format!("parse witness args failed: {e}"))?;
+        .map_err(|e| VerifyError::NotBindTx(format!("parse witness args failed: {e}")))?;
 
     let input_type = if let Some(input_type) = witness_args.input_type().to_opt() {
         input_type
     } else {
-        return Err("input_type is None".to_string());
+        return Err(VerifyError::NotBindTx("input_type is None".to_string()));
     };
     let bind_info_with_sig_bytes = input_type.raw_data().to_vec();
-    let bind_info_with_sig = BindInfoWithSig::from_compatible_slice(&bind_info_with_sig_bytes)
-        .map_err(|e| format!("parse bind info with sig failed: {e}"))?;
+    let bind_info_with_sig =
+        SignedBindInfo::parse(&bind_info_with_sig_bytes).map_err(VerifyError::NotBindTx)?;
 
-    // input lock script must be equal to output lock script
-    let pre_tx_hash = tx.inputs[0].previous_output.tx_hash.clone();
-    let pre_index: u32 = tx.inputs[0].previous_output.index.into();
-    let pre_tx = get_tx(ckb_client, pre_tx_hash)
-        .await
-        .map_err(|e| format!("get_tx failed: {e}"))?;
-    let pre_output = pre_tx.outputs[pre_index as usize].clone();
-    let pre_output_lock_script = pre_output.lock.clone();
+    // transfer to itself, every output goes back to the lock of the inputs
     let output_lock_script = tx.outputs[0].lock.clone();
-
-    // transfer to itself
-    if pre_output_lock_script != output_lock_script {
-        return Err("pre_output_lock_script not equal output_lock_script".to_string());
+    if tx
+        .outputs
+        .iter()
+        .any(|output| output.lock != output_lock_script)
+    {
+        return Err(VerifyError::Rejected(
+            RejectReason::LockMismatch,
+            "output lock scripts are not all equal".to_string(),
+        ));
+    }
+    for input in &tx.inputs {
+        let pre_out_point = &input.previous_output;
+        let pre_output = chain
+            .get_previous_output(pre_out_point)
+            .await
+            .map_err(|e| VerifyError::Transient(e.to_string()))?
+            .ok_or_else(|| {
+                VerifyError::Transient(format!(
+                    "previous output {:#x}:{} not found",
+                    pre_out_point.tx_hash,
+                    u32::from(pre_out_point.index)
+                ))
+            })?;
+        if pre_output.lock != output_lock_script {
+            return Err(VerifyError::Rejected(
+                RejectReason::LockMismatch,
+                "pre_output_lock_script not equal output_lock_script".to_string(),
+            ));
+        }
     }
 
-    let bind_info = bind_info_with_sig.bind_info();
-    let sig = bind_info_with_sig.sig();
-    let sig_bytes = sig.raw_data().to_vec();
-    let bind_info_bytes = bind_info.as_slice();
+    let bind_info_bytes = bind_info_with_sig.bind_info();
+    let sig_bytes = bind_info_with_sig.sig();
 
     // transfer is to of bind info
-    if bind_info.to().code_hash().raw_data() != output_lock_script.code_hash.as_bytes()
-        || u8::from(bind_info.to().hash_type()) != output_lock_script.hash_type.clone() as u8
-        || bind_info.to().args().raw_data() != output_lock_script.args.as_bytes()
+    let bind_to = bind_info_with_sig.to();
+    if bind_to.code_hash().raw_data() != output_lock_script.code_hash.as_bytes()
+        || u8::from(bind_to.hash_type()) != output_lock_script.hash_type.clone() as u8
+        || bind_to.args().raw_data() != output_lock_script.args.as_bytes()
     {
-        return Err("bind_info_to not equal output_lock_script".to_string());
+        return Err(VerifyError::Rejected(
+            RejectReason::BindToMismatch,
+            "bind_info_to not equal output_lock_script".to_string(),
+        ));
+    }
+
+    // v2 is only valid on the network and in the domain it was signed for
+    if let SignedBindInfo::V2(v2) = &bind_info_with_sig {
+        let bind_info = v2.bind_info();
+        let bind_network = bind_info.network().raw_data();
+        if bind_network.as_ref() != network.to_str().as_bytes() {
+            return Err(VerifyError::Rejected(
+                RejectReason::NetworkMismatch,
+                format!(
+                    "signed for network {}",
+                    String::from_utf8_lossy(&bind_network)
+                ),
+            ));
+        }
+        let bind_domain = bind_info.domain().raw_data();
+        if bind_domain.as_ref() != options.domain.as_bytes() {
+            return Err(VerifyError::Rejected(
+                RejectReason::DomainMismatch,
+                format!(
+                    "signed for domain {}",
+                    String::from_utf8_lossy(&bind_domain)
+                ),
+            ));
+        }
     }
 
-    let timestamp = u64::from_le_bytes(
-        bind_info
-            .timestamp()
-            .as_slice()
-            .try_into()
-            .map_err(|e| format!("parse timestamp failed: {e}"))?,
-    );
-    let to_addr = calculate_address(&output_lock_script.into(), network);
+    let timestamp = bind_info_with_sig.timestamp();
+    let to_lock: packed::Script = output_lock_script.into();
+    let to_addr = calculate_address(&to_lock, network);
 
     // verify sig and recover from address
-    let message = format!("0x{}", hex::encode(bind_info_bytes));
+    let message = bind_info_message(&bind_info_bytes);
     // raw neuron signature not Signature Object
-    let from_addr = if sig_bytes.len() == 65 {
-        let message = format!("Nervos Message:{message}");
-        let message_hash = ckb_hash::blake2b_256(message.as_bytes());
-        let mut sig: [u8; 64] = [0; 64];
-        sig.copy_from_slice(&sig_bytes[0..64]);
-        let recovery_id: u8 = sig_bytes[64];
-        let ret = recover(message_hash, sig, recovery_id);
-        if let Err(_e) = ret {
-            return Err("recover error".to_string());
-        }
-        let pubkey = ret.unwrap();
+    let (from_lock, sig_scheme) = if sig_bytes.len() == 65 {
+        let pubkey =
+            secp256k1_recover(ckb_message_hash(&message), &sig_bytes[0..64], sig_bytes[64])
+                .map_err(|e| VerifyError::Rejected(RejectReason::InvalidSignature, e))?;
         let pubkey_hash = ckb_hash::blake2b_256(pubkey.serialize());
         let from_args = pubkey_hash[0..20].to_vec();
-        calculate_from_address(&from_args, network).to_string()
+        let from_lock = calculate_from_address(&from_args, network).payload().into();
+        (from_lock, "Neuron".to_string())
+    } else if is_multisig_sig(&sig_bytes) {
+        // neuron signatures of multisig members
+        let multisig = parse_multisig_sig(&sig_bytes)
+            .map_err(|e| VerifyError::Rejected(RejectReason::InvalidSignature, e))?;
+        let from_lock = recover_multisig_lock(&message, &multisig).map_err(|e| {
+            VerifyError::Rejected(
+                RejectReason::InvalidSignature,
+                format!("multisig error: {e}"),
+            )
+        })?;
+        (from_lock, "NeuronMultisig".to_string())
     } else {
         // sig is Signature Object
+        let sig_obj = parse_signature_object(&sig_bytes)
+            .map_err(|e| VerifyError::Rejected(RejectReason::InvalidSignature, e))?;
+        let from_lock = match recover_lock_script(&message, &sig_obj, network) {
+            Ok(from_lock) => from_lock,
+            // fallback to recoverysig service for signatures can not be verified natively
+            Err(RecoverError::Unsupported(e)) => {
+                let Some(recovery_url) = &options.recovery_url else {
+                    return Err(VerifyError::Rejected(
+                        RejectReason::UnsupportedSignature,
+                        format!("recover error: unsupported {e}"),
+                    ));
+                };
+                // sig arg must no 0x prefix
+                let sig_arg = hex::encode(sig_bytes);
+                // call http://localhost:3000/recover?msg=<msg>&sig=<sig_arg>
+                let ret = reqwest::get(format!(
+                    "{recovery_url}/recover?msg={message}&sig={sig_arg}"
+                ))
+                .await;
+                if let Err(e) = ret {
+                    return Err(VerifyError::Transient(format!("call recover error: {e:?}")));
+                }
+                let resp = ret.unwrap();
+                if resp.status().is_server_error() {
+                    return Err(VerifyError::Transient(format!(
+                        "recover service error: {}",
+                        resp.status()
+                    )));
+                }
+                let json: serde_json::Value = resp
+                    .json()
+                    .await
+                    .map_err(|e| VerifyError::Transient(format!("parse json error: {e:?}")))?;
+                let address = json["address"].as_str().ok_or_else(|| {
+                    VerifyError::Rejected(
+                        RejectReason::RecoveryFailed,
+                        format!("address not found: {json}"),
+                    )
+                })?;
+                let address = Address::from_str(address).map_err(|e| {
+                    VerifyError::Rejected(
+                        RejectReason::RecoveryFailed,
+                        format!("parse address {address} failed: {e}"),
+                    )
+                })?;
+                address.payload().into()
+            }
+            Err(e) => {
+                return Err(VerifyError::Rejected(
+                    RejectReason::InvalidSignature,
+                    format!("recover error: {e}"),
+                ));
+            }
+        };
+        (from_lock, sig_obj.sign_type)
+    };
 
-        // sig arg must no 0x prefix
-        let sig_arg = hex::encode(sig_bytes);
-        // call http://localhost:3000/recover?msg=<msg>&sig=<sig_arg>
-        let ret = reqwest::get(format!(
-            "{recovery_url}/recover?msg={message}&sig={sig_arg}"
-        ))
-        .await;
-        if let Err(e) = ret {
-            return Err(format!("call recover error: {e:?}"));
+    // the signer must be the from declared in v2
+    if let SignedBindInfo::V2(v2) = &bind_info_with_sig
+        && v2.bind_info().from().as_slice() != from_lock.as_slice()
+    {
+        return Err(VerifyError::Rejected(
+            RejectReason::FromMismatch,
+            format!(
+                "signed by {} not the declared from",
+                calculate_address(&from_lock, network)
+            ),
+        ));
+    }
+    let from_addr = calculate_address(&from_lock, network);
+
+    Ok(BindRecord {
+        from: from_addr.to_string(),
+        to: to_addr.to_string(),
+        timestamp,
+        from_lock,
+        to_lock,
+        sig_scheme,
+        payload: bind_info_with_sig_bytes,
+        version: bind_info_with_sig.version(),
+        digest: ckb_hash::blake2b_256(&bind_info_bytes),
+    })
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::chain::{MemoryChain, PrevOutput, TxBundle};
+    use crate::message::{
+        bind_info_message, build_bind_info, build_bind_info_v2, compose, multisig_sig, witness_args,
+    };
+    use crate::signature::neuron_sign_message;
+    use ckb_sdk::{Since, constants::MultisigScript, unlock::MultisigConfig};
+    use ckb_types::core::{TransactionBuilder, TransactionView};
+    use ckb_types::prelude::IntoTransactionView;
+    use secp256k1::{PublicKey, Secp256k1, SecretKey};
+
+    pub(crate) const TIMESTAMP: u64 = 1760432079687;
+
+    pub(crate) fn lock_of(sk: &SecretKey) -> packed::Script {
+        let pk = PublicKey::from_secret_key(&Secp256k1::new(), sk);
+        let args = &ckb_hash::blake2b_256(pk.serialize())[0..20];
+        calculate_from_address(args, NetworkType::Testnet)
+            .payload()
+            .into()
+    }
+
+    // bind tx spending a cell of owner_lock back to output_lock, signed by from_sk
+    pub(crate) fn bind_tx(
+        chain: &mut MemoryChain,
+        from_sk: &SecretKey,
+        owner_lock: &packed::Script,
+        output_lock: &packed::Script,
+    ) -> Transaction {
+        let bind_info = build_bind_info(output_lock, TIMESTAMP);
+        bind_tx_signed_by(
+            chain,
+            owner_lock,
+            output_lock,
+            bind_info.as_slice(),
+            |message| neuron_sign_message(message, from_sk).to_vec(),
+        )
+    }
+
+    fn bind_tx_signed_by(
+        chain: &mut MemoryChain,
+        owner_lock: &packed::Script,
+        output_lock: &packed::Script,
+        bind_info: &[u8],
+        sign: impl Fn(&str) -> Vec<u8>,
+    ) -> Transaction {
+        let pre_tx: TransactionView = TransactionBuilder::default()
+            .output(
+                packed::CellOutput::new_builder()
+                    .lock(owner_lock.clone())
+                    .build(),
+            )
+            .output_data(Bytes::new().pack())
+            .build();
+        let pre_tx_hash = chain.insert_transaction(pre_tx.data().into());
+
+        let sig = sign(&bind_info_message(bind_info));
+        let witness = witness_args(&compose(bind_info, &sig).unwrap());
+
+        TransactionBuilder::default()
+            .input(packed::CellInput::new(
+                packed::OutPoint::new(pre_tx_hash.pack(), 0),
+                0,
+            ))
+            .output(
+                packed::CellOutput::new_builder()
+                    .lock(output_lock.clone())
+                    .build(),
+            )
+            .output_data(Bytes::new().pack())
+            .witness(witness.as_bytes().pack())
+            .build()
+            .data()
+            .into()
+    }
+
+    #[test]
+    fn test_normalize_address() {
+        let lock = lock_of(&SecretKey::from_slice(&[0x11; 32]).unwrap());
+        let full = calculate_address(&lock, NetworkType::Testnet).to_string();
+        let short = Address::new(
+            NetworkType::Testnet,
+            AddressPayload::from(lock.clone()),
+            false,
+        );
+        let mainnet = calculate_address(&lock, NetworkType::Mainnet);
+        for address in [full.clone(), short.to_string(), mainnet.to_string()] {
+            assert_eq!(
+                normalize_address(&address, NetworkType::Testnet).unwrap(),
+                full
+            );
         }
-        let resp = ret.unwrap();
-        let json: serde_json::Value = resp
-            .json()
+        assert!(normalize_address("ckt1", NetworkType::Testnet).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_verify_neuron_bind_tx() {
+        let mut chain = MemoryChain::new();
+        let from_sk = SecretKey::from_slice(&[0x11; 32]).unwrap();
+        let to_lock = lock_of(&SecretKey::from_slice(&[0x22; 32]).unwrap());
+        let tx = bind_tx(&mut chain, &from_sk, &to_lock, &to_lock);
+
+        let record = verify_tx(&chain, NetworkType::Testnet, &tx, &VerifyOptions::default())
             .await
-            .map_err(|e| format!("parse json error: {e:?}"))?;
-        json["address"]
-            .as_str()
-            .map(|s| s.to_string())
-            .ok_or("address not found".to_string())?
-    };
+            .unwrap();
+        assert_eq!(record.from_lock, lock_of(&from_sk));
+        assert_eq!(record.to_lock, to_lock);
+        assert_eq!(
+            record.to,
+            calculate_address(&to_lock, NetworkType::Testnet).to_string()
+        );
+        assert_eq!(record.timestamp, TIMESTAMP);
+        assert_eq!(record.sig_scheme, "Neuron");
+    }
+
+    #[tokio::test]
+    async fn test_verify_multisig_bind_tx() {
+        let mut chain = MemoryChain::new();
+        let to_lock = lock_of(&SecretKey::from_slice(&[0x22; 32]).unwrap());
+        let keys: Vec<SecretKey> = (1..=3u8)
+            .map(|i| SecretKey::from_slice(&[i; 32]).unwrap())
+            .collect();
+        let config = MultisigConfig::new_with(
+            MultisigScript::Legacy,
+            keys.iter()
+                .map(|key| H160::from_slice(&lock_of(key).args().raw_data()).unwrap())
+                .collect(),
+            0,
+            2,
+        )
+        .unwrap();
+        let v1 = build_bind_info(&to_lock, TIMESTAMP);
+        let v1 = v1.as_slice();
+        let tx = bind_tx_signed_by(&mut chain, &to_lock, &to_lock, v1, |message| {
+            let sigs = [&keys[2], &keys[1]].map(|key| neuron_sign_message(message, key).to_vec());
+            multisig_sig(
+                MultisigScript::Legacy,
+                &config.to_witness_data(),
+                None,
+                &sigs,
+            )
+        });
+
+        let record = verify_tx(&chain, NetworkType::Testnet, &tx, &VerifyOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(
+            record.from,
+            config
+                .to_address(NetworkType::Testnet, MultisigScript::Legacy, None)
+                .to_string()
+        );
+        assert_eq!(record.sig_scheme, "NeuronMultisig");
+
+        // time-locked multisig v2 address of the same members
+        let epoch = 0x0100;
+        let since = Since::new_absolute_epoch(epoch).value();
+        let tx = bind_tx_signed_by(&mut chain, &to_lock, &to_lock, v1, |message| {
+            let sigs = [&keys[0], &keys[2]].map(|key| neuron_sign_message(message, key).to_vec());
+            multisig_sig(
+                MultisigScript::V2,
+                &config.to_witness_data(),
+                Some(since),
+                &sigs,
+            )
+        });
+        let record = verify_tx(&chain, NetworkType::Testnet, &tx, &VerifyOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(
+            record.from,
+            config
+                .to_address(NetworkType::Testnet, MultisigScript::V2, Some(epoch))
+                .to_string()
+        );
+
+        // one member short of the threshold
+        let tx = bind_tx_signed_by(&mut chain, &to_lock, &to_lock, v1, |message| {
+            let mut sig = multisig_sig(
+                MultisigScript::Legacy,
+                &config.to_witness_data(),
+                None,
+                &[neuron_sign_message(message, &keys[0]).to_vec()],
+            );
+            sig.extend_from_slice(&[0u8; 65]);
+            sig
+        });
+        let ret = verify_tx(&chain, NetworkType::Testnet, &tx, &VerifyOptions::default()).await;
+        assert!(matches!(
+            ret,
+            Err(VerifyError::Rejected(RejectReason::InvalidSignature, _))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_verify_v2_bind_tx() {
+        let mut chain = MemoryChain::new();
+        let from_sk = SecretKey::from_slice(&[0x11; 32]).unwrap();
+        let from_lock = lock_of(&from_sk);
+        let to_lock = lock_of(&SecretKey::from_slice(&[0x22; 32]).unwrap());
+        let mut bind_tx_v2 = |declared_from: &packed::Script, network, domain| {
+            let bind_info = build_bind_info_v2(declared_from, &to_lock, TIMESTAMP, network, domain);
+            bind_tx_signed_by(
+                &mut chain,
+                &to_lock,
+                &to_lock,
+                bind_info.as_slice(),
+                |message| neuron_sign_message(message, &from_sk).to_vec(),
+            )
+        };
+        let valid = bind_tx_v2(&from_lock, NetworkType::Testnet, DEFAULT_DOMAIN);
+        let other_from = bind_tx_v2(&to_lock, NetworkType::Testnet, DEFAULT_DOMAIN);
+        let mainnet = bind_tx_v2(&from_lock, NetworkType::Mainnet, DEFAULT_DOMAIN);
+        let other_app = bind_tx_v2(&from_lock, NetworkType::Testnet, "other-app");
+
+        let options = VerifyOptions::default();
+        let record = verify_tx(&chain, NetworkType::Testnet, &valid, &options)
+            .await
+            .unwrap();
+        assert_eq!(record.from_lock, from_lock);
+        assert_eq!(record.to_lock, to_lock);
+        assert_eq!(record.timestamp, TIMESTAMP);
+        assert_eq!(record.version, 2);
+
+        for (tx, reason) in [
+            (other_from, RejectReason::FromMismatch),
+            (mainnet, RejectReason::NetworkMismatch),
+            (other_app.clone(), RejectReason::DomainMismatch),
+        ] {
+            let ret = verify_tx(&chain, NetworkType::Testnet, &tx, &options).await;
+            assert!(
+                matches!(&ret, Err(VerifyError::Rejected(r, _)) if *r == reason),
+                "{ret:?}"
+            );
+        }
+        // the domain is configurable
+        let options = VerifyOptions {
+            domain: "other-app".to_string(),
+            ..Default::default()
+        };
+        assert!(
+            verify_tx(&chain, NetworkType::Testnet, &other_app, &options)
+                .await
+                .is_ok()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_verify_rejects_lock_mismatch() {
+        let mut chain = MemoryChain::new();
+        let from_sk = SecretKey::from_slice(&[0x11; 32]).unwrap();
+        let owner_lock = lock_of(&SecretKey::from_slice(&[0x33; 32]).unwrap());
+        let to_lock = lock_of(&SecretKey::from_slice(&[0x22; 32]).unwrap());
+        let tx = bind_tx(&mut chain, &from_sk, &owner_lock, &to_lock);
+
+        let ret = verify_tx(&chain, NetworkType::Testnet, &tx, &VerifyOptions::default()).await;
+        assert!(matches!(
+            ret,
+            Err(VerifyError::Rejected(RejectReason::LockMismatch, _))
+        ));
+    }
 
-    Ok((from_addr, to_addr.to_string(), timestamp))
+    #[tokio::test]
+    async fn test_verify_lock_group_shape() {
+        let mut chain = MemoryChain::new();
+        let from_sk = SecretKey::from_slice(&[0x11; 32]).unwrap();
+        let to_lock = lock_of(&SecretKey::from_slice(&[0x22; 32]).unwrap());
+        let other_lock = lock_of(&SecretKey::from_slice(&[0x33; 32]).unwrap());
+        let tx = bind_tx(&mut chain, &from_sk, &to_lock, &to_lock);
+
+        // a wallet merging two cells of the to lock and splitting the change
+        let cell =
+            |lock: &packed::Script| packed::CellOutput::new_builder().lock(lock.clone()).build();
+        let pre_tx: TransactionView = TransactionBuilder::default()
+            .output(cell(&to_lock))
+            .output(cell(&other_lock))
+            .outputs_data([Bytes::new().pack(), Bytes::new().pack()])
+            .build();
+        let pre_tx_hash = chain.insert_transaction(pre_tx.data().into());
+        let input = |index: u32| {
+            packed::CellInput::new(packed::OutPoint::new(pre_tx_hash.pack(), index), 0)
+        };
+        let multi = packed::Transaction::from(tx)
+            .into_view()
+            .as_advanced_builder()
+            .input(input(0))
+            .output(cell(&to_lock))
+            .output_data(Bytes::new().pack())
+            .build();
+        let multi_tx: Transaction = multi.data().into();
+
+        let record = verify_tx(
+            &chain,
+            NetworkType::Testnet,
+            &multi_tx,
+            &VerifyOptions::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(record.from_lock, lock_of(&from_sk));
+        assert_eq!(record.to_lock, to_lock);
+
+        let strict = VerifyOptions {
+            shape: ShapePolicy::Strict,
+            ..Default::default()
+        };
+        let ret = verify_tx(&chain, NetworkType::Testnet, &multi_tx, &strict).await;
+        assert!(matches!(ret, Err(VerifyError::NotBindTx(_))));
+
+        // an output leaving the lock group
+        let leaking: Transaction = multi
+            .as_advanced_builder()
+            .output(cell(&other_lock))
+            .output_data(Bytes::new().pack())
+            .build()
+            .data()
+            .into();
+        let ret = verify_tx(
+            &chain,
+            NetworkType::Testnet,
+            &leaking,
+            &VerifyOptions::default(),
+        )
+        .await;
+        assert!(matches!(
+            ret,
+            Err(VerifyError::Rejected(RejectReason::LockMismatch, _))
+        ));
+
+        // an input of another lock
+        let foreign: Transaction = multi
+            .as_advanced_builder()
+            .input(input(1))
+            .build()
+            .data()
+            .into();
+        let ret = verify_tx(
+            &chain,
+            NetworkType::Testnet,
+            &foreign,
+            &VerifyOptions::default(),
+        )
+        .await;
+        assert!(matches!(
+            ret,
+            Err(VerifyError::Rejected(RejectReason::LockMismatch, _))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_verify_missing_previous_output_is_transient() {
+        let mut chain = MemoryChain::new();
+        let from_sk = SecretKey::from_slice(&[0x11; 32]).unwrap();
+        let to_lock = lock_of(&SecretKey::from_slice(&[0x22; 32]).unwrap());
+        let tx = bind_tx(&mut chain, &from_sk, &to_lock, &to_lock);
+
+        let ret = verify_tx(
+            &MemoryChain::new(),
+            NetworkType::Testnet,
+            &tx,
+            &VerifyOptions::default(),
+        )
+        .await;
+        assert!(matches!(ret, Err(VerifyError::Transient(_))));
+    }
+
+    #[tokio::test]
+    async fn test_verify_tx_bundle_offline() {
+        let mut chain = MemoryChain::new();
+        let from_sk = SecretKey::from_slice(&[0x11; 32]).unwrap();
+        let to_lock = lock_of(&SecretKey::from_slice(&[0x22; 32]).unwrap());
+        let tx = bind_tx(&mut chain, &from_sk, &to_lock, &to_lock);
+
+        // tx file and prev outputs file as pasted by a user
+        let out_point = tx.inputs[0].previous_output.clone();
+        let output = chain
+            .get_previous_output(&out_point)
+            .await
+            .unwrap()
+            .unwrap();
+        let dir = std::env::temp_dir().join(format!("abtest_bundle_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let tx_file = dir.join("tx.json");
+        let prev_file = dir.join("prev.json");
+        std::fs::write(&tx_file, serde_json::to_string(&tx).unwrap()).unwrap();
+        std::fs::write(
+            &prev_file,
+            serde_json::to_string(&[PrevOutput { out_point, output }]).unwrap(),
+        )
+        .unwrap();
+
+        let bundle = TxBundle::load(&tx_file, Some(&prev_file)).unwrap();
+        let ret = verify_tx(
+            &bundle.chain(),
+            NetworkType::Testnet,
+            &bundle.transaction,
+            &VerifyOptions::default(),
+        )
+        .await;
+        assert_eq!(ret.unwrap().to_lock, to_lock);
+
+        // the same as one self-contained bundle
+        let bundle_file = dir.join("bundle.json");
+        std::fs::write(&bundle_file, serde_json::to_string(&bundle).unwrap()).unwrap();
+        let bundle = TxBundle::load(&bundle_file, None).unwrap();
+        assert_eq!(bundle.prev_outputs.len(), 1);
+        let ret = verify_tx(
+            &bundle.chain(),
+            NetworkType::Testnet,
+            &bundle.transaction,
+            &VerifyOptions::default(),
+        )
+        .await;
+        assert_eq!(ret.unwrap().to_lock, to_lock);
+
+        // without prev outputs nothing to compare the lock with
+        let bundle = TxBundle::load(&tx_file, None).unwrap();
+        let ret = verify_tx(
+            &bundle.chain(),
+            NetworkType::Testnet,
+            &bundle.transaction,
+            &VerifyOptions::default(),
+        )
+        .await;
+        assert!(matches!(ret, Err(VerifyError::Transient(_))));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
 }